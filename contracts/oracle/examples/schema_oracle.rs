@@ -3,7 +3,10 @@ use std::fs::create_dir_all;
 
 use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
 
-use oracle::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
+use oracle::msg::{
+    AllPricesResponse, ConfigResponse, ExecuteMsg, InstantiateMsg, PausedFeedResponse, PriceHistoryResponse, PriceResponse,
+    PublishersResponse, QueryMsg, SubscribersResponse, TwapResponse,
+};
 use oracle::state::State;
 
 fn main() {
@@ -16,4 +19,12 @@ fn main() {
     export_schema(&schema_for!(ExecuteMsg), &out_dir);
     export_schema(&schema_for!(QueryMsg), &out_dir);
     export_schema(&schema_for!(State), &out_dir);
+    export_schema(&schema_for!(ConfigResponse), &out_dir);
+    export_schema(&schema_for!(PriceResponse), &out_dir);
+    export_schema(&schema_for!(AllPricesResponse), &out_dir);
+    export_schema(&schema_for!(PublishersResponse), &out_dir);
+    export_schema(&schema_for!(PriceHistoryResponse), &out_dir);
+    export_schema(&schema_for!(TwapResponse), &out_dir);
+    export_schema(&schema_for!(SubscribersResponse), &out_dir);
+    export_schema(&schema_for!(PausedFeedResponse), &out_dir);
 }
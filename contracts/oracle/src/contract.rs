@@ -1,85 +1,794 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult, to_binary,
+    attr, Addr, Binary, Decimal, Deps, DepsMut, Empty, Env, Fraction, MessageInfo, Order, Reply,
+    Response, StdError, StdResult, Storage, SubMsg, to_binary, Uint128, Uint256, WasmMsg,
 };
 use cw2::set_contract_version;
+use cw_storage_plus::{Bound, U64Key};
+use std::convert::TryInto;
 
 use crate::error::{ContractError};
-use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg, PriceResponse};
-use crate::state::{STATE, State};
+use crate::msg::{
+    AllPricesResponse, CallbackMsg, CircuitBreaker, ConfigResponse, ExecuteMsg, InstantiateMsg, Pair, PairPriceResponse, QueryMsg,
+    PausedFeedResponse, PriceHistoryResponse, PriceObservationResponse, PriceResponse, PriceStatus, PublisherInfo,
+    PublishersResponse, SubscribersResponse, TwapResponse,
+};
+use crate::state::{
+    PausedFeed, PriceObservation, PriceSubmission, PAIRS, PAUSED, PRICE_HISTORY, PUBLISHERS, STATE,
+    State, SUBMISSIONS, SUBSCRIBER_COUNT, SUBSCRIBERS, SUBSCRIPTION_REQUESTS,
+};
 
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:oracle";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+const DEFAULT_MAX_SUBMISSION_AGE: u64 = 3600;
+const DEFAULT_MAX_HISTORY_LENGTH: u32 = 100;
+// gas each subscriber callback may use, so a subscriber cannot exhaust the update's gas
+const CALLBACK_GAS_LIMIT: u64 = 300_000;
+
+// settings for pagination
+const MAX_LIMIT: u32 = 30;
+const DEFAULT_LIMIT: u32 = 10;
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
-    // TODO: instantiate contract
     let initial_state = State {
-      owner: info.sender,
-      price: msg.price
+      owner: info.sender.clone(),
+      pending_owner: None,
+      max_submission_age: msg.max_submission_age.unwrap_or(DEFAULT_MAX_SUBMISSION_AGE),
+      max_history_length: msg.max_history_length.unwrap_or(DEFAULT_MAX_HISTORY_LENGTH),
+      circuit_breaker: msg.circuit_breaker.unwrap_or_default(),
+      min_price: msg.min_price,
+      max_price: msg.max_price,
     };
+    validate_price_bounds(initial_state.min_price, initial_state.max_price)?;
 
     STATE.save(deps.storage, &initial_state)?;
 
-    Ok(Response::new()
-      .add_attribute("owner", initial_state.owner)
-      .add_attribute("price", initial_state.price.to_string()))
+    // the instantiator is the first publisher and seeds the feeds with the initial prices
+    PUBLISHERS.save(deps.storage, &info.sender, &Empty {})?;
+    for publisher in msg.publishers.unwrap_or_default() {
+      let publisher = deps.api.addr_validate(&publisher)?;
+      PUBLISHERS.save(deps.storage, &publisher, &Empty {})?;
+    }
+
+    let mut response = Response::new().add_attributes(vec![
+      ("action", "instantiate"),
+      ("owner", initial_state.owner.as_str()),
+      ("publisher", info.sender.as_str()),
+    ]);
+    for pair_price in msg.prices {
+      if !price_in_bounds(&initial_state, pair_price.price) {
+        return Err(ContractError::PriceInstantiationError {});
+      }
+      let pair_key = pair_price.pair.key(deps.api)?;
+      save_submission(deps.storage, &env, &pair_key, &pair_price.pair, &info.sender, pair_price.price)?;
+      let price = aggregate_price(deps.storage, &env, &pair_key)?.price;
+      record_observation(deps.storage, &env, &pair_key, price)?;
+      response = response.add_attribute(format!("price:{}", pair_key), pair_price.price.to_string());
+    }
+
+    Ok(response)
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
     deps: DepsMut,
-    _env: Env,
-    _info: MessageInfo,
+    env: Env,
+    info: MessageInfo,
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
-    //TODO: execute try_update_price
     match msg {
-      ExecuteMsg::UpdatePrice { price } => { 
-        let update_fn = |state: State| -> Result<State, ContractError> {
-          Ok(State { price, owner: state.owner })
-        };
+      ExecuteMsg::UpdatePrice { pair, price } => try_update_price(deps, env, info, pair, price),
+      ExecuteMsg::AddPublisher { address } => try_add_publisher(deps, info, address),
+      ExecuteMsg::RemovePublisher { address } => try_remove_publisher(deps, info, address),
+      ExecuteMsg::UpdateCircuitBreaker { circuit_breaker } => {
+        try_update_circuit_breaker(deps, info, circuit_breaker)
+      }
+      ExecuteMsg::UpdatePriceBounds { min_price, max_price } => {
+        try_update_price_bounds(deps, info, min_price, max_price)
+      }
+      ExecuteMsg::ConfirmPrice { pair } => try_confirm_price(deps, env, info, pair),
+      ExecuteMsg::ProposeNewOwner { owner } => try_propose_new_owner(deps, info, owner),
+      ExecuteMsg::AcceptOwnership {} => try_accept_ownership(deps, info),
+      ExecuteMsg::Subscribe {} => try_subscribe(deps, info),
+      ExecuteMsg::ApproveSubscriber { address } => try_approve_subscriber(deps, info, address),
+      ExecuteMsg::RemoveSubscriber { address } => try_remove_subscriber(deps, info, address),
+    }
+}
+
+pub fn try_update_price(deps: DepsMut, env: Env, info: MessageInfo, pair: Pair, price: Decimal) -> Result<Response, ContractError> {
+  let state = STATE.load(deps.storage)?;
+  if !PUBLISHERS.has(deps.storage, &info.sender) {
+    return Err(ContractError::Unauthorized {});
+  }
+  if !price_in_bounds(&state, price) {
+    return Err(ContractError::PriceUpdateError {});
+  }
+
+  let pair_key = pair.key(deps.api)?;
+  let old_price = last_observation(deps.storage, &pair_key)?.map(|(_, observation)| observation.price);
+  save_submission(deps.storage, &env, &pair_key, &pair, &info.sender, price)?;
 
-        STATE.update(deps.storage, update_fn)?;
+  let mut attributes = vec![
+    attr("action", "update_price"),
+    attr("pair", &pair_key),
+    attr("publisher", info.sender.as_str()),
+    attr("price", price.to_string()),
+  ];
+
+  // submissions keep being collected while paused, but nothing is published until the owner confirms
+  let status = if PAUSED.has(deps.storage, &pair_key) {
+    "paused"
+  } else {
+    let aggregated_price = aggregate_price(deps.storage, &env, &pair_key)?.price;
+    match check_circuit_breaker(deps.storage, &env, &pair_key, aggregated_price)? {
+      Some(reason) => {
+        let paused = PausedFeed { rejected_price: aggregated_price, paused_at: env.block.time.seconds() };
+        PAUSED.save(deps.storage, &pair_key, &paused)?;
+        attributes.push(attr("circuit_breaker", reason));
+        "paused"
       }
-    };
+      None => {
+        record_observation(deps.storage, &env, &pair_key, aggregated_price)?;
+        "active"
+      }
+    }
+  };
+
+  // old_price and new_price are the published price of the pair before and after the update
+  let new_price = last_observation(deps.storage, &pair_key)?.map(|(_, observation)| observation.price);
+  if let Some(old_price) = old_price {
+    attributes.push(attr("old_price", old_price.to_string()));
+  }
+  if let Some(new_price) = new_price {
+    attributes.push(attr("new_price", new_price.to_string()));
+  }
+  attributes.push(attr("status", status));
+
+  // subscribers only hear about prices that were actually published
+  let callbacks = match status {
+    "active" => subscriber_callbacks(deps.storage, &env, &pair, &pair_key)?,
+    _ => vec![],
+  };
+
+  Ok(Response::new().add_attributes(attributes).add_submessages(callbacks))
+}
+
+pub fn try_update_price_bounds(
+  deps: DepsMut,
+  info: MessageInfo,
+  min_price: Option<Decimal>,
+  max_price: Option<Decimal>,
+) -> Result<Response, ContractError> {
+  let mut state = STATE.load(deps.storage)?;
+  if info.sender != state.owner {
+    return Err(ContractError::Unauthorized {});
+  }
+
+  validate_price_bounds(min_price, max_price)?;
+  state.min_price = min_price;
+  state.max_price = max_price;
+  STATE.save(deps.storage, &state)?;
+
+  Ok(Response::new().add_attribute("method", "try_update_price_bounds"))
+}
+
+pub fn try_propose_new_owner(deps: DepsMut, info: MessageInfo, owner: String) -> Result<Response, ContractError> {
+  let mut state = STATE.load(deps.storage)?;
+  if info.sender != state.owner {
+    return Err(ContractError::Unauthorized {});
+  }
+
+  let pending_owner = deps.api.addr_validate(&owner)?;
+  state.pending_owner = Some(pending_owner.clone());
+  STATE.save(deps.storage, &state)?;
+
+  Ok(Response::new().add_attributes(vec![
+    ("method", "try_propose_new_owner"),
+    ("pending_owner", pending_owner.as_str()),
+  ]))
+}
+
+pub fn try_accept_ownership(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+  let mut state = STATE.load(deps.storage)?;
+  let pending_owner = state.pending_owner.take().ok_or(ContractError::NoPendingOwner {})?;
+  if info.sender != pending_owner {
+    return Err(ContractError::Unauthorized {});
+  }
+
+  let previous_owner = std::mem::replace(&mut state.owner, pending_owner);
+  STATE.save(deps.storage, &state)?;
+
+  Ok(Response::new().add_attributes(vec![
+    ("method", "try_accept_ownership"),
+    ("previous_owner", previous_owner.as_str()),
+    ("owner", state.owner.as_str()),
+  ]))
+}
+
+pub fn try_update_circuit_breaker(deps: DepsMut, info: MessageInfo, circuit_breaker: CircuitBreaker) -> Result<Response, ContractError> {
+  let mut state = STATE.load(deps.storage)?;
+  if info.sender != state.owner {
+    return Err(ContractError::Unauthorized {});
+  }
+
+  state.circuit_breaker = circuit_breaker;
+  STATE.save(deps.storage, &state)?;
+
+  Ok(Response::new().add_attribute("method", "try_update_circuit_breaker"))
+}
+
+pub fn try_confirm_price(deps: DepsMut, env: Env, info: MessageInfo, pair: Pair) -> Result<Response, ContractError> {
+  let owner = STATE.load(deps.storage)?.owner;
+  if info.sender != owner {
+    return Err(ContractError::Unauthorized {});
+  }
+
+  let pair_key = pair.key(deps.api)?;
+  if !PAUSED.has(deps.storage, &pair_key) {
+    return Err(ContractError::FeedNotPaused {});
+  }
+  PAUSED.remove(deps.storage, &pair_key);
+
+  // the feed resumes from the current aggregate, which may have moved on since the breaker tripped
+  let price = aggregate_price(deps.storage, &env, &pair_key)?.price;
+  record_observation(deps.storage, &env, &pair_key, price)?;
+  let callbacks = subscriber_callbacks(deps.storage, &env, &pair, &pair_key)?;
+
+  Ok(Response::new()
+    .add_attributes(vec![
+      ("method", "try_confirm_price"),
+      ("pair", &pair_key),
+      ("price", &price.to_string()),
+    ])
+    .add_submessages(callbacks))
+}
+
+pub fn try_subscribe(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+  if SUBSCRIBERS.has(deps.storage, &info.sender) || SUBSCRIPTION_REQUESTS.has(deps.storage, &info.sender) {
+    return Err(ContractError::AlreadySubscribed {});
+  }
+  SUBSCRIPTION_REQUESTS.save(deps.storage, &info.sender, &Empty {})?;
+
+  Ok(Response::new().add_attributes(vec![
+    ("method", "try_subscribe"),
+    ("subscriber", info.sender.as_str()),
+  ]))
+}
+
+pub fn try_approve_subscriber(deps: DepsMut, info: MessageInfo, address: String) -> Result<Response, ContractError> {
+  let owner = STATE.load(deps.storage)?.owner;
+  if info.sender != owner {
+    return Err(ContractError::Unauthorized {});
+  }
+
+  let subscriber = deps.api.addr_validate(&address)?;
+  if !SUBSCRIPTION_REQUESTS.has(deps.storage, &subscriber) {
+    return Err(ContractError::SubscriptionNotRequested {});
+  }
+  SUBSCRIPTION_REQUESTS.remove(deps.storage, &subscriber);
+
+  let id = SUBSCRIBER_COUNT.may_load(deps.storage)?.unwrap_or_default() + 1;
+  SUBSCRIBER_COUNT.save(deps.storage, &id)?;
+  SUBSCRIBERS.save(deps.storage, &subscriber, &id)?;
+
+  Ok(Response::new().add_attributes(vec![
+    ("method", "try_approve_subscriber"),
+    ("subscriber", subscriber.as_str()),
+  ]))
+}
+
+pub fn try_remove_subscriber(deps: DepsMut, info: MessageInfo, address: String) -> Result<Response, ContractError> {
+  let owner = STATE.load(deps.storage)?.owner;
+  let subscriber = deps.api.addr_validate(&address)?;
+  if info.sender != owner && info.sender != subscriber {
+    return Err(ContractError::Unauthorized {});
+  }
+
+  // pending requests can be withdrawn the same way
+  if SUBSCRIBERS.has(deps.storage, &subscriber) {
+    SUBSCRIBERS.remove(deps.storage, &subscriber);
+  } else if SUBSCRIPTION_REQUESTS.has(deps.storage, &subscriber) {
+    SUBSCRIPTION_REQUESTS.remove(deps.storage, &subscriber);
+  } else {
+    return Err(ContractError::SubscriberNotFound {});
+  }
+
+  Ok(Response::new().add_attributes(vec![
+    ("method", "try_remove_subscriber"),
+    ("subscriber", subscriber.as_str()),
+  ]))
+}
+
+// Subscriber callbacks only reply when they fail, which is caught here so the price update still goes through.
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, ContractError> {
+  let reply_id = msg.id;
+  let error = match msg.result.into_result() {
+    Err(error) => error,
+    Ok(_) => return Ok(Response::new()),
+  };
+  let subscriber = SUBSCRIBERS
+    .range(deps.storage, None, None, Order::Ascending)
+    .find_map(|item| match item {
+      Ok((key, id)) if id == reply_id => Some(String::from_utf8(key)),
+      _ => None,
+    })
+    .transpose()
+    .map_err(StdError::from)?
+    .unwrap_or_default();
+
+  Ok(Response::new().add_attributes(vec![
+    ("method", "reply"),
+    ("failed_subscriber", &subscriber),
+    ("error", &error),
+  ]))
+}
+
+pub fn try_add_publisher(deps: DepsMut, info: MessageInfo, address: String) -> Result<Response, ContractError> {
+  let owner = STATE.load(deps.storage)?.owner;
+  if info.sender != owner {
+    return Err(ContractError::Unauthorized {});
+  }
+
+  let publisher = deps.api.addr_validate(&address)?;
+  if PUBLISHERS.has(deps.storage, &publisher) {
+    return Err(ContractError::PublisherAlreadyExists {});
+  }
+  PUBLISHERS.save(deps.storage, &publisher, &Empty {})?;
 
-    Ok(Response::new())
+  Ok(Response::new().add_attributes(vec![
+    ("method", "try_add_publisher"),
+    ("publisher", publisher.as_str()),
+  ]))
+}
+
+pub fn try_remove_publisher(deps: DepsMut, info: MessageInfo, address: String) -> Result<Response, ContractError> {
+  let owner = STATE.load(deps.storage)?.owner;
+  if info.sender != owner {
+    return Err(ContractError::Unauthorized {});
+  }
+
+  let publisher = deps.api.addr_validate(&address)?;
+  if !PUBLISHERS.has(deps.storage, &publisher) {
+    return Err(ContractError::PublisherNotFound {});
+  }
+  PUBLISHERS.remove(deps.storage, &publisher);
+
+  // drop the publisher's prices so they no longer count towards any median
+  let pair_keys = PAIRS
+    .keys(deps.storage, None, None, Order::Ascending)
+    .map(|key| Ok(String::from_utf8(key)?))
+    .collect::<StdResult<Vec<_>>>()?;
+  for pair_key in pair_keys {
+    SUBMISSIONS.remove(deps.storage, (&pair_key, &publisher));
+  }
+
+  Ok(Response::new().add_attributes(vec![
+    ("method", "try_remove_publisher"),
+    ("publisher", publisher.as_str()),
+  ]))
+}
+
+// A callback to every subscriber with the price just published for the pair.
+fn subscriber_callbacks(storage: &dyn Storage, env: &Env, pair: &Pair, pair_key: &str) -> StdResult<Vec<SubMsg>> {
+  let callback = to_binary(&CallbackMsg::PriceUpdate {
+    pair: pair.clone(),
+    price: aggregate_price(storage, env, pair_key)?,
+  })?;
+
+  SUBSCRIBERS
+    .range(storage, None, None, Order::Ascending)
+    .map(|item| {
+      let (key, id) = item?;
+      let msg = WasmMsg::Execute {
+        contract_addr: String::from_utf8(key)?,
+        msg: callback.clone(),
+        funds: vec![],
+      };
+      Ok(SubMsg::reply_on_error(msg, id).with_gas_limit(CALLBACK_GAS_LIMIT))
+    })
+    .collect()
+}
+
+// Prices have to be positive and within the configured bounds, if any.
+fn price_in_bounds(state: &State, price: Decimal) -> bool {
+  !price.is_zero()
+    && state.min_price.map_or(true, |min_price| price >= min_price)
+    && state.max_price.map_or(true, |max_price| price <= max_price)
+}
+
+fn validate_price_bounds(min_price: Option<Decimal>, max_price: Option<Decimal>) -> Result<(), ContractError> {
+  match (min_price, max_price) {
+    (Some(min_price), Some(max_price)) if min_price > max_price => Err(ContractError::InvalidPriceBounds {}),
+    _ => Ok(()),
+  }
+}
+
+// Stores a publisher's price for the pair.
+fn save_submission(
+  storage: &mut dyn Storage,
+  env: &Env,
+  pair_key: &str,
+  pair: &Pair,
+  publisher: &Addr,
+  price: Decimal,
+) -> StdResult<()> {
+  let submission = PriceSubmission {
+    price,
+    last_updated_height: env.block.height,
+    last_updated_time: env.block.time.seconds(),
+  };
+  PAIRS.save(storage, pair_key, pair)?;
+  SUBMISSIONS.save(storage, (pair_key, publisher), &submission)?;
+  Ok(())
+}
+
+// Returns the name of the first limit the new aggregated price breaches, if any. Changes are
+// measured against the last published price and every price published during the window.
+fn check_circuit_breaker(storage: &dyn Storage, env: &Env, pair_key: &str, new_price: Decimal) -> StdResult<Option<&'static str>> {
+  let circuit_breaker = STATE.load(storage)?.circuit_breaker;
+
+  if let Some(max_change) = circuit_breaker.max_update_change {
+    if let Some((_, previous)) = last_observation(storage, pair_key)? {
+      if relative_change(previous.price, new_price) > max_change {
+        return Ok(Some("max_update_change"));
+      }
+    }
+  }
+
+  if let Some(max_change) = circuit_breaker.max_window_change {
+    let window_start = env.block.time.seconds().saturating_sub(circuit_breaker.window);
+    let observations = window_observations(storage, pair_key, window_start)?;
+    if observations.iter().any(|(_, price)| relative_change(*price, new_price) > max_change) {
+      return Ok(Some("max_window_change"));
+    }
+  }
+
+  Ok(None)
+}
+
+// |new - old| / old
+fn relative_change(old: Decimal, new: Decimal) -> Decimal {
+  let diff = if new > old { new - old } else { old - new };
+  if diff.is_zero() {
+    Decimal::zero()
+  } else if old.is_zero() {
+    Decimal::MAX
+  } else {
+    // divide in 256 bits, a jump off a tiny price can be larger than Decimal::MAX
+    let ratio = Uint128::from(diff.numerator()).full_mul(Decimal::one().denominator())
+      / Uint256::from(old.numerator());
+    ratio
+      .try_into()
+      .map(|atomics: Uint128| Decimal::from_ratio(atomics, Decimal::one().denominator()))
+      .unwrap_or(Decimal::MAX)
+  }
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    // TODO
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
-      QueryMsg::QueryPrice {} => { 
-        let res = STATE.load(deps.storage)?.price;
-        let price_response = PriceResponse { price: res };
-        to_binary(&price_response)
+      QueryMsg::QueryPrice { pair } => {
+        let pair_key = pair.key(deps.api)?;
+        let price = pair_price(deps.storage, &env, &pair_key)?
+          .ok_or_else(|| StdError::generic_err(format!("No fresh price submissions for {}", pair_key)))?;
+        to_binary(&price)
       }
+      QueryMsg::AllPrices { start_after, limit } => {
+        to_binary(&query_all_prices(deps, env, start_after, limit)?)
+      }
+      QueryMsg::QueryPublishers { pair } => to_binary(&query_publishers(deps, pair)?),
+      QueryMsg::PriceHistory { pair, start_after, limit } => {
+        to_binary(&query_price_history(deps, pair, start_after, limit)?)
+      }
+      QueryMsg::Twap { pair, window } => to_binary(&query_twap(deps, env, pair, window)?),
+      QueryMsg::QueryConfig {} => to_binary(&query_config(deps)?),
+      QueryMsg::QuerySubscribers {} => to_binary(&query_subscribers(deps)?),
+      QueryMsg::PausedFeed { pair } => to_binary(&query_paused_feed(deps, pair)?),
     }
 }
 
+// The price the oracle publishes for the pair: the current aggregate, or the last accepted price
+// with a paused status while the pair's circuit breaker is tripped.
+fn pair_price(storage: &dyn Storage, env: &Env, pair_key: &str) -> StdResult<Option<PriceResponse>> {
+  if !PAUSED.has(storage, pair_key) {
+    return try_aggregate_price(storage, env, pair_key);
+  }
+
+  Ok(last_observation(storage, pair_key)?.map(|(time, observation)| PriceResponse {
+    price: observation.price,
+    last_updated_height: observation.height,
+    last_updated_time: time,
+    status: PriceStatus::Paused,
+  }))
+}
+
+// Median of the fresh publisher submissions for the pair at the current block time.
+fn aggregate_price(storage: &dyn Storage, env: &Env, pair_key: &str) -> StdResult<PriceResponse> {
+  try_aggregate_price(storage, env, pair_key)?
+    .ok_or_else(|| StdError::generic_err(format!("No fresh price submissions for {}", pair_key)))
+}
+
+fn try_aggregate_price(storage: &dyn Storage, env: &Env, pair_key: &str) -> StdResult<Option<PriceResponse>> {
+  let max_submission_age = STATE.load(storage)?.max_submission_age;
+  let now = env.block.time.seconds();
+
+  let submissions: Vec<PriceSubmission> = SUBMISSIONS
+    .prefix(pair_key)
+    .range(storage, None, None, Order::Ascending)
+    .filter_map(|item| match item {
+      Ok((_, submission)) if now.saturating_sub(submission.last_updated_time) <= max_submission_age => {
+        Some(Ok(submission))
+      }
+      Ok(_) => None,
+      Err(err) => Some(Err(err)),
+    })
+    .collect::<StdResult<_>>()?;
+
+  let mut prices: Vec<Decimal> = submissions.iter().map(|s| s.price).collect();
+  let price = match median(&mut prices) {
+    Some(price) => price,
+    None => return Ok(None),
+  };
+
+  // report the oldest submission so consumers judge staleness conservatively
+  let oldest = submissions.iter().min_by_key(|s| s.last_updated_time).unwrap();
+  Ok(Some(PriceResponse {
+    price,
+    last_updated_height: oldest.last_updated_height,
+    last_updated_time: oldest.last_updated_time,
+    status: PriceStatus::Active,
+  }))
+}
+
+fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
+  let state = STATE.load(deps.storage)?;
+  let publishers = PUBLISHERS
+    .keys(deps.storage, None, None, Order::Ascending)
+    .map(|key| deps.api.addr_validate(&String::from_utf8(key)?))
+    .collect::<StdResult<_>>()?;
+
+  Ok(ConfigResponse {
+    owner: state.owner,
+    pending_owner: state.pending_owner,
+    publishers,
+    max_submission_age: state.max_submission_age,
+    max_history_length: state.max_history_length,
+    circuit_breaker: state.circuit_breaker,
+    min_price: state.min_price,
+    max_price: state.max_price,
+  })
+}
+
+fn query_subscribers(deps: Deps) -> StdResult<SubscribersResponse> {
+  let subscribers = SUBSCRIBERS
+    .keys(deps.storage, None, None, Order::Ascending)
+    .map(|key| deps.api.addr_validate(&String::from_utf8(key)?))
+    .collect::<StdResult<_>>()?;
+  let requests = SUBSCRIPTION_REQUESTS
+    .keys(deps.storage, None, None, Order::Ascending)
+    .map(|key| deps.api.addr_validate(&String::from_utf8(key)?))
+    .collect::<StdResult<_>>()?;
+
+  Ok(SubscribersResponse { subscribers, requests })
+}
+
+fn query_paused_feed(deps: Deps, pair: Pair) -> StdResult<PausedFeedResponse> {
+  let pair_key = pair.key(deps.api)?;
+  Ok(PausedFeedResponse { paused: PAUSED.may_load(deps.storage, &pair_key)? })
+}
+
+fn query_all_prices(deps: Deps, env: Env, start_after: Option<Pair>, limit: Option<u32>) -> StdResult<AllPricesResponse> {
+  let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+  let start = match start_after {
+    Some(pair) => Some(Bound::exclusive(pair.key(deps.api)?)),
+    None => None,
+  };
+
+  let prices = PAIRS
+    .range(deps.storage, start, None, Order::Ascending)
+    .take(limit)
+    .map(|item| {
+      let (key, pair) = item?;
+      Ok(PairPriceResponse {
+        pair,
+        price: pair_price(deps.storage, &env, &String::from_utf8(key)?)?,
+      })
+    })
+    .collect::<StdResult<_>>()?;
+
+  Ok(AllPricesResponse { prices })
+}
+
+fn query_publishers(deps: Deps, pair: Pair) -> StdResult<PublishersResponse> {
+  let pair_key = pair.key(deps.api)?;
+  let publishers = PUBLISHERS
+    .keys(deps.storage, None, None, Order::Ascending)
+    .map(|key| {
+      let address = deps.api.addr_validate(&String::from_utf8(key)?)?;
+      let submission = SUBMISSIONS.may_load(deps.storage, (&pair_key, &address))?;
+      Ok(PublisherInfo {
+        address,
+        price: submission.as_ref().map(|s| s.price),
+        last_updated_height: submission.as_ref().map(|s| s.last_updated_height),
+        last_updated_time: submission.as_ref().map(|s| s.last_updated_time),
+      })
+    })
+    .collect::<StdResult<_>>()?;
+
+  Ok(PublishersResponse { publishers })
+}
+
+fn query_price_history(deps: Deps, pair: Pair, start_after: Option<u64>, limit: Option<u32>) -> StdResult<PriceHistoryResponse> {
+  let pair_key = pair.key(deps.api)?;
+  let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+  let start = start_after.map(Bound::exclusive_int);
+
+  let observations = PRICE_HISTORY
+    .prefix(&pair_key)
+    .range(deps.storage, start, None, Order::Ascending)
+    .take(limit)
+    .map(|item| {
+      let (key, observation) = item?;
+      Ok(PriceObservationResponse {
+        time: parse_time_key(&key)?,
+        height: observation.height,
+        price: observation.price,
+      })
+    })
+    .collect::<StdResult<_>>()?;
+
+  Ok(PriceHistoryResponse { observations })
+}
+
+fn query_twap(deps: Deps, env: Env, pair: Pair, window: u64) -> StdResult<TwapResponse> {
+  let pair_key = pair.key(deps.api)?;
+  let end_time = env.block.time.seconds();
+  let window_start = end_time.saturating_sub(window);
+
+  let observations = window_observations(deps.storage, &pair_key, window_start)?;
+
+  let (first_time, _) = observations
+    .first()
+    .ok_or_else(|| StdError::generic_err(format!("No price observations for {}", pair_key)))?;
+  let start_time = (*first_time).max(window_start);
+
+  // sum of price * seconds, in the atomic units of Decimal
+  let mut weighted_sum = Uint128::zero();
+  for (i, (time, price)) in observations.iter().enumerate() {
+    let from = (*time).max(start_time);
+    let to = observations.get(i + 1).map(|(next, _)| *next).unwrap_or(end_time);
+    let weighted_price = Uint128::from(price.numerator()).checked_mul(Uint128::from(to - from))?;
+    weighted_sum = weighted_sum.checked_add(weighted_price)?;
+  }
+
+  let price = match end_time - start_time {
+    // everything was observed in the current block
+    0 => observations.last().unwrap().1,
+    duration => Decimal::from_ratio(
+      weighted_sum,
+      Uint128::from(duration).checked_mul(Uint128::from(Decimal::one().denominator()))?,
+    ),
+  };
+
+  Ok(TwapResponse { price, start_time, end_time })
+}
+
+// The observation in effect when the window opens, followed by every observation inside it.
+fn window_observations(storage: &dyn Storage, pair_key: &str, window_start: u64) -> StdResult<Vec<(u64, Decimal)>> {
+  let history = PRICE_HISTORY.prefix(pair_key);
+  history
+    .range(storage, None, Some(Bound::inclusive_int(window_start)), Order::Descending)
+    .take(1)
+    .chain(history.range(storage, Some(Bound::exclusive_int(window_start)), None, Order::Ascending))
+    .map(|item| {
+      let (key, observation) = item?;
+      Ok((parse_time_key(&key)?, observation.price))
+    })
+    .collect()
+}
+
+fn last_observation(storage: &dyn Storage, pair_key: &str) -> StdResult<Option<(u64, PriceObservation)>> {
+  PRICE_HISTORY
+    .prefix(pair_key)
+    .range(storage, None, None, Order::Descending)
+    .next()
+    .map(|item| {
+      let (key, observation) = item?;
+      Ok((parse_time_key(&key)?, observation))
+    })
+    .transpose()
+}
+
+// Appends an accepted price of the pair to its history, dropping the oldest observations past the limit.
+fn record_observation(storage: &mut dyn Storage, env: &Env, pair_key: &str, price: Decimal) -> StdResult<()> {
+  let observation = PriceObservation { price, height: env.block.height };
+  PRICE_HISTORY.save(storage, (pair_key, U64Key::new(env.block.time.seconds())), &observation)?;
+
+  // the last accepted price is always kept, it is what a paused pair reports
+  let max_history_length = STATE.load(storage)?.max_history_length.max(1) as usize;
+  let expired: Vec<Vec<u8>> = PRICE_HISTORY
+    .prefix(pair_key)
+    .keys(storage, None, None, Order::Descending)
+    .skip(max_history_length)
+    .collect();
+  for key in expired {
+    PRICE_HISTORY.remove(storage, (pair_key, U64Key::from(key)));
+  }
+
+  Ok(())
+}
+
+fn parse_time_key(key: &[u8]) -> StdResult<u64> {
+  let bytes: [u8; 8] = key
+    .try_into()
+    .map_err(|_| StdError::generic_err("Invalid price history key"))?;
+  Ok(u64::from_be_bytes(bytes))
+}
+
+// Median of the given prices; the two middle values are averaged (rounding down) for an even count.
+fn median(prices: &mut [Decimal]) -> Option<Decimal> {
+  if prices.is_empty() {
+    return None;
+  }
+
+  prices.sort_unstable();
+  let mid = prices.len() / 2;
+  if prices.len() % 2 == 1 {
+    Some(prices[mid])
+  } else {
+    let (low, high) = (prices[mid - 1], prices[mid]);
+    Some(low + (high - low) / Uint128::new(2))
+  }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::msg::PairPrice;
     use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
-    use cosmwasm_std::{coins, from_binary};
+    use cosmwasm_std::{coins, from_binary, ContractResult};
+    use cw_asset::AssetInfo;
+
+    fn lemon_luna() -> Pair {
+      Pair::new(AssetInfo::cw20(Addr::unchecked("lemon")), AssetInfo::native("uluna"))
+    }
+
+    fn luna_ust() -> Pair {
+      Pair::new(AssetInfo::native("uluna"), AssetInfo::native("uusd"))
+    }
+
+    fn default_instantiate_msg() -> InstantiateMsg {
+      InstantiateMsg {
+        prices: vec![PairPrice { pair: lemon_luna(), price: Decimal::percent(1000) }],
+        publishers: None,
+        max_submission_age: None,
+        max_history_length: None,
+        circuit_breaker: None,
+        min_price: None,
+        max_price: None,
+      }
+    }
 
     #[test]
     fn proper_initialization() {
         let mut deps = mock_dependencies(&[]);
 
-        let msg = InstantiateMsg { price: 17 };
+        let msg = InstantiateMsg {
+            prices: vec![PairPrice { pair: lemon_luna(), price: Decimal::percent(1700) }],
+            ..default_instantiate_msg()
+        };
         let info = mock_info("creator", &coins(1000, "earth"));
 
         // we can just call .unwrap() to assert this was a success
@@ -87,31 +796,448 @@ mod tests {
         assert_eq!(0, res.messages.len());
 
         // it worked, let's query the state
-        let res = query(deps.as_ref(), mock_env(), QueryMsg::QueryPrice {}).unwrap();
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::QueryPrice { pair: lemon_luna() }).unwrap();
         let price_response: PriceResponse = from_binary(&res).unwrap();
-        assert_eq!(17, price_response.price);
+        assert_eq!(Decimal::percent(1700), price_response.price);
+        assert_eq!(mock_env().block.height, price_response.last_updated_height);
+        assert_eq!(mock_env().block.time.seconds(), price_response.last_updated_time);
     }
 
     #[test]
     fn update_price() {
       let mut deps = mock_dependencies(&coins(2, "token"));
 
-      let msg = InstantiateMsg { price: 17 };
+      let msg = InstantiateMsg {
+        prices: vec![PairPrice { pair: lemon_luna(), price: Decimal::percent(1700) }],
+        publishers: Some(vec![String::from("oracle")]),
+        ..default_instantiate_msg()
+      };
       let info = mock_info("creator", &coins(1000, "earth"));
       let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
-      
+
       // it worked, let's query the state
-      let res = query(deps.as_ref(), mock_env(), QueryMsg::QueryPrice {}).unwrap();
+      let res = query(deps.as_ref(), mock_env(), QueryMsg::QueryPrice { pair: lemon_luna() }).unwrap();
       let price_response: PriceResponse = from_binary(&res).unwrap();
-      assert_eq!(17, price_response.price);
+      assert_eq!(Decimal::percent(1700), price_response.price);
 
       let info = mock_info("oracle", &coins(2, "token"));
-      let msg = ExecuteMsg::UpdatePrice { price: 18 };
-      let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+      let msg = ExecuteMsg::UpdatePrice { pair: lemon_luna(), price: Decimal::percent(1900) };
+      let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+      assert!(res.attributes.contains(&attr("action", "update_price")));
+      assert!(res.attributes.contains(&attr("publisher", "oracle")));
+      assert!(res.attributes.contains(&attr("old_price", "17")));
+      assert!(res.attributes.contains(&attr("new_price", "18")));
 
-      // it worked, let's query the state
-      let res = query(deps.as_ref(), mock_env(), QueryMsg::QueryPrice {}).unwrap();
+      // the median of 17 and 19
+      let res = query(deps.as_ref(), mock_env(), QueryMsg::QueryPrice { pair: lemon_luna() }).unwrap();
+      let price_response: PriceResponse = from_binary(&res).unwrap();
+      assert_eq!(Decimal::percent(1800), price_response.price);
+
+      // unknown publishers cannot move the price
+      let info = mock_info("anyone", &[]);
+      let msg = ExecuteMsg::UpdatePrice { pair: lemon_luna(), price: Decimal::percent(100) };
+      let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+      assert!(matches!(err, ContractError::Unauthorized {}));
+    }
+
+    #[test]
+    fn rejects_prices_out_of_bounds() {
+      let mut deps = mock_dependencies(&[]);
+
+      let msg = InstantiateMsg {
+        prices: vec![PairPrice { pair: lemon_luna(), price: Decimal::zero() }],
+        ..default_instantiate_msg()
+      };
+      let err = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap_err();
+      assert!(matches!(err, ContractError::PriceInstantiationError {}));
+
+      let msg = InstantiateMsg {
+        min_price: Some(Decimal::percent(500)),
+        max_price: Some(Decimal::percent(2000)),
+        ..default_instantiate_msg()
+      };
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      for price in [Decimal::zero(), Decimal::percent(499), Decimal::percent(2001)] {
+        let msg = ExecuteMsg::UpdatePrice { pair: lemon_luna(), price };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::PriceUpdateError {}));
+      }
+
+      let msg = ExecuteMsg::UpdatePriceBounds { min_price: Some(Decimal::percent(3000)), max_price: Some(Decimal::percent(2000)) };
+      let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap_err();
+      assert!(matches!(err, ContractError::InvalidPriceBounds {}));
+
+      // lifting the maximum lets the higher price through
+      let msg = ExecuteMsg::UpdatePriceBounds { min_price: Some(Decimal::percent(500)), max_price: None };
+      let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+      let msg = ExecuteMsg::UpdatePrice { pair: lemon_luna(), price: Decimal::percent(2001) };
+      let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+    }
+
+    #[test]
+    fn median_ignores_single_outlier_and_stale_prices() {
+      let mut deps = mock_dependencies(&[]);
+
+      let msg = InstantiateMsg {
+        publishers: Some(vec![String::from("alice"), String::from("bob")]),
+        max_submission_age: Some(60),
+        ..default_instantiate_msg()
+      };
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      let msg = ExecuteMsg::UpdatePrice { pair: lemon_luna(), price: Decimal::percent(1100) };
+      let _res = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), msg).unwrap();
+      let msg = ExecuteMsg::UpdatePrice { pair: lemon_luna(), price: Decimal::percent(100_000) };
+      let _res = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), msg).unwrap();
+
+      let res = query(deps.as_ref(), mock_env(), QueryMsg::QueryPrice { pair: lemon_luna() }).unwrap();
+      let price_response: PriceResponse = from_binary(&res).unwrap();
+      assert_eq!(Decimal::percent(1100), price_response.price);
+
+      // only bob submits again later, everything else has gone stale
+      let mut env = mock_env();
+      env.block.time = env.block.time.plus_seconds(120);
+      let msg = ExecuteMsg::UpdatePrice { pair: lemon_luna(), price: Decimal::percent(1200) };
+      let _res = execute(deps.as_mut(), env.clone(), mock_info("bob", &[]), msg).unwrap();
+
+      let res = query(deps.as_ref(), env.clone(), QueryMsg::QueryPrice { pair: lemon_luna() }).unwrap();
+      let price_response: PriceResponse = from_binary(&res).unwrap();
+      assert_eq!(Decimal::percent(1200), price_response.price);
+      assert_eq!(env.block.time.seconds(), price_response.last_updated_time);
+
+      env.block.time = env.block.time.plus_seconds(120);
+      assert!(query(deps.as_ref(), env, QueryMsg::QueryPrice { pair: lemon_luna() }).is_err());
+    }
+
+    #[test]
+    fn manage_publishers() {
+      let mut deps = mock_dependencies(&[]);
+
+      let msg = default_instantiate_msg();
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      let msg = ExecuteMsg::AddPublisher { address: String::from("alice") };
+      let err = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), msg.clone()).unwrap_err();
+      assert!(matches!(err, ContractError::Unauthorized {}));
+      let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      let res = query(deps.as_ref(), mock_env(), QueryMsg::QueryPublishers { pair: lemon_luna() }).unwrap();
+      let publishers: PublishersResponse = from_binary(&res).unwrap();
+      assert_eq!(2, publishers.publishers.len());
+      assert_eq!(None, publishers.publishers[0].price);
+      assert_eq!(Some(Decimal::percent(1000)), publishers.publishers[1].price);
+
+      let msg = ExecuteMsg::RemovePublisher { address: String::from("alice") };
+      let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg.clone()).unwrap();
+      let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap_err();
+      assert!(matches!(err, ContractError::PublisherNotFound {}));
+
+      let msg = ExecuteMsg::UpdatePrice { pair: lemon_luna(), price: Decimal::percent(100) };
+      let err = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), msg).unwrap_err();
+      assert!(matches!(err, ContractError::Unauthorized {}));
+    }
+
+    #[test]
+    fn transfer_ownership() {
+      let mut deps = mock_dependencies(&[]);
+
+      let msg = InstantiateMsg {
+        prices: vec![],
+        publishers: Some(vec![String::from("alice")]),
+        ..default_instantiate_msg()
+      };
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      let msg = ExecuteMsg::AcceptOwnership {};
+      let err = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), msg).unwrap_err();
+      assert!(matches!(err, ContractError::NoPendingOwner {}));
+
+      let msg = ExecuteMsg::ProposeNewOwner { owner: String::from("bob") };
+      let err = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), msg.clone()).unwrap_err();
+      assert!(matches!(err, ContractError::Unauthorized {}));
+      let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      // ownership only moves once the proposed owner accepts
+      let msg = ExecuteMsg::AcceptOwnership {};
+      let err = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), msg.clone()).unwrap_err();
+      assert!(matches!(err, ContractError::Unauthorized {}));
+      let config: ConfigResponse = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::QueryConfig {}).unwrap()).unwrap();
+      assert_eq!(Addr::unchecked("creator"), config.owner);
+      assert_eq!(Some(Addr::unchecked("bob")), config.pending_owner);
+      assert_eq!(vec![Addr::unchecked("alice"), Addr::unchecked("creator")], config.publishers);
+
+      let _res = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), msg).unwrap();
+      let config: ConfigResponse = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::QueryConfig {}).unwrap()).unwrap();
+      assert_eq!(Addr::unchecked("bob"), config.owner);
+      assert_eq!(None, config.pending_owner);
+
+      // the new owner manages publishers, the old one cannot
+      let msg = ExecuteMsg::RemovePublisher { address: String::from("alice") };
+      let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg.clone()).unwrap_err();
+      assert!(matches!(err, ContractError::Unauthorized {}));
+      let _res = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), msg).unwrap();
+
+      // owning the oracle doesn't make bob a publisher until bob is added to the list
+      let msg = ExecuteMsg::UpdatePrice { pair: lemon_luna(), price: Decimal::percent(1000) };
+      let err = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), msg.clone()).unwrap_err();
+      assert!(matches!(err, ContractError::Unauthorized {}));
+      let add = ExecuteMsg::AddPublisher { address: String::from("bob") };
+      let _res = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), add).unwrap();
+      let _res = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), msg).unwrap();
+    }
+
+    #[test]
+    fn subscribers_are_called_back_on_published_prices() {
+      let mut deps = mock_dependencies(&[]);
+
+      let msg = InstantiateMsg {
+        circuit_breaker: Some(CircuitBreaker { max_update_change: Some(Decimal::percent(10)), max_window_change: None, window: 0 }),
+        ..default_instantiate_msg()
+      };
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      let _res = execute(deps.as_mut(), mock_env(), mock_info("consumer", &[]), ExecuteMsg::Subscribe {}).unwrap();
+      let err = execute(deps.as_mut(), mock_env(), mock_info("consumer", &[]), ExecuteMsg::Subscribe {}).unwrap_err();
+      assert!(matches!(err, ContractError::AlreadySubscribed {}));
+
+      // nothing is sent until the owner approves
+      let msg = ExecuteMsg::UpdatePrice { pair: lemon_luna(), price: Decimal::percent(1050) };
+      let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+      assert_eq!(0, res.messages.len());
+
+      let msg = ExecuteMsg::ApproveSubscriber { address: String::from("consumer") };
+      let err = execute(deps.as_mut(), mock_env(), mock_info("consumer", &[]), msg.clone()).unwrap_err();
+      assert!(matches!(err, ContractError::Unauthorized {}));
+      let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      let res: SubscribersResponse = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::QuerySubscribers {}).unwrap()).unwrap();
+      assert_eq!(vec![Addr::unchecked("consumer")], res.subscribers);
+      assert!(res.requests.is_empty());
+
+      let msg = ExecuteMsg::UpdatePrice { pair: lemon_luna(), price: Decimal::percent(1100) };
+      let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+      assert_eq!(1, res.messages.len());
+      let callback = to_binary(&CallbackMsg::PriceUpdate {
+        pair: lemon_luna(),
+        price: PriceResponse {
+          price: Decimal::percent(1100),
+          last_updated_height: mock_env().block.height,
+          last_updated_time: mock_env().block.time.seconds(),
+          status: PriceStatus::Active,
+        },
+      }).unwrap();
+      let expected = WasmMsg::Execute { contract_addr: String::from("consumer"), msg: callback, funds: vec![] };
+      assert_eq!(SubMsg::reply_on_error(expected, 1).with_gas_limit(CALLBACK_GAS_LIMIT), res.messages[0]);
+
+      // a price held back by the circuit breaker is not pushed
+      let msg = ExecuteMsg::UpdatePrice { pair: lemon_luna(), price: Decimal::percent(5000) };
+      let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+      assert_eq!(0, res.messages.len());
+
+      // a failing subscriber is reported without reverting the update
+      let msg = Reply { id: 1, result: ContractResult::Err(String::from("out of gas")) };
+      let res = reply(deps.as_mut(), mock_env(), msg).unwrap();
+      assert!(res.attributes.contains(&attr("failed_subscriber", "consumer")));
+
+      let msg = ExecuteMsg::RemoveSubscriber { address: String::from("consumer") };
+      let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), msg.clone()).unwrap_err();
+      assert!(matches!(err, ContractError::Unauthorized {}));
+      let _res = execute(deps.as_mut(), mock_env(), mock_info("consumer", &[]), msg.clone()).unwrap();
+      let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap_err();
+      assert!(matches!(err, ContractError::SubscriberNotFound {}));
+    }
+
+    #[test]
+    fn price_history_is_bounded() {
+      let mut deps = mock_dependencies(&[]);
+      let mut env = mock_env();
+
+      let msg = InstantiateMsg { max_history_length: Some(3), ..default_instantiate_msg() };
+      let _res = instantiate(deps.as_mut(), env.clone(), mock_info("creator", &[]), msg).unwrap();
+
+      for price in 11..15 {
+        let price = Decimal::percent(price * 100);
+        env.block.time = env.block.time.plus_seconds(10);
+        env.block.height += 1;
+        let msg = ExecuteMsg::UpdatePrice { pair: lemon_luna(), price };
+        let _res = execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), msg).unwrap();
+      }
+
+      let msg = QueryMsg::PriceHistory { pair: lemon_luna(), start_after: None, limit: None };
+      let res: PriceHistoryResponse = from_binary(&query(deps.as_ref(), env.clone(), msg).unwrap()).unwrap();
+      let prices: Vec<Decimal> = res.observations.iter().map(|o| o.price).collect();
+      assert_eq!(vec![Decimal::percent(1200), Decimal::percent(1300), Decimal::percent(1400)], prices);
+      assert_eq!(env.block.time.seconds(), res.observations[2].time);
+
+      let msg = QueryMsg::PriceHistory { pair: lemon_luna(), start_after: Some(res.observations[0].time), limit: Some(1) };
+      let res: PriceHistoryResponse = from_binary(&query(deps.as_ref(), env, msg).unwrap()).unwrap();
+      assert_eq!(1, res.observations.len());
+      assert_eq!(Decimal::percent(1300), res.observations[0].price);
+    }
+
+    #[test]
+    fn twap() {
+      let mut deps = mock_dependencies(&[]);
+      let mut env = mock_env();
+      let start = env.block.time.seconds();
+
+      let msg = default_instantiate_msg();
+      let _res = instantiate(deps.as_mut(), env.clone(), mock_info("creator", &[]), msg).unwrap();
+
+      // nothing has elapsed yet, so the spot price is the average
+      let res: TwapResponse = from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::Twap { pair: lemon_luna(), window: 60 }).unwrap()).unwrap();
+      assert_eq!(Decimal::percent(1000), res.price);
+
+      env.block.time = env.block.time.plus_seconds(30);
+      let msg = ExecuteMsg::UpdatePrice { pair: lemon_luna(), price: Decimal::percent(4000) };
+      let _res = execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), msg).unwrap();
+
+      // 10 for 30 seconds and 40 for 10 seconds
+      env.block.time = env.block.time.plus_seconds(10);
+      let res: TwapResponse = from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::Twap { pair: lemon_luna(), window: 60 }).unwrap()).unwrap();
+      assert_eq!(Decimal::percent(1750), res.price);
+      assert_eq!(start, res.start_time);
+
+      // 10 for the first 10 seconds of the window and 40 for the remaining 10
+      let res: TwapResponse = from_binary(&query(deps.as_ref(), env, QueryMsg::Twap { pair: lemon_luna(), window: 20 }).unwrap()).unwrap();
+      assert_eq!(Decimal::percent(2500), res.price);
+    }
+
+    #[test]
+    fn prices_are_tracked_per_pair() {
+      let mut deps = mock_dependencies(&[]);
+
+      let msg = InstantiateMsg { publishers: Some(vec![String::from("alice")]), ..default_instantiate_msg() };
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      // no one has priced luna in ust yet
+      assert!(query(deps.as_ref(), mock_env(), QueryMsg::QueryPrice { pair: luna_ust() }).is_err());
+
+      let msg = ExecuteMsg::UpdatePrice { pair: luna_ust(), price: Decimal::percent(9000) };
+      let _res = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), msg).unwrap();
+
+      let res = query(deps.as_ref(), mock_env(), QueryMsg::QueryPrice { pair: luna_ust() }).unwrap();
+      let price_response: PriceResponse = from_binary(&res).unwrap();
+      assert_eq!(Decimal::percent(9000), price_response.price);
+
+      let res = query(deps.as_ref(), mock_env(), QueryMsg::QueryPrice { pair: lemon_luna() }).unwrap();
+      let price_response: PriceResponse = from_binary(&res).unwrap();
+      assert_eq!(Decimal::percent(1000), price_response.price);
+
+      // pairs are ordered by key, and cw20 sorts before native
+      let msg = QueryMsg::AllPrices { start_after: None, limit: None };
+      let res: AllPricesResponse = from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+      assert_eq!(2, res.prices.len());
+      assert_eq!(lemon_luna(), res.prices[0].pair);
+      assert_eq!(Some(Decimal::percent(1000)), res.prices[0].price.as_ref().map(|p| p.price));
+      assert_eq!(luna_ust(), res.prices[1].pair);
+      assert_eq!(Some(Decimal::percent(9000)), res.prices[1].price.as_ref().map(|p| p.price));
+
+      let msg = QueryMsg::AllPrices { start_after: Some(lemon_luna()), limit: Some(1) };
+      let res: AllPricesResponse = from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+      assert_eq!(1, res.prices.len());
+      assert_eq!(luna_ust(), res.prices[0].pair);
+    }
+
+    #[test]
+    fn circuit_breaker_pauses_feed_until_confirmed() {
+      let mut deps = mock_dependencies(&[]);
+
+      let msg = InstantiateMsg {
+        circuit_breaker: Some(CircuitBreaker { max_update_change: Some(Decimal::percent(10)), max_window_change: None, window: 0 }),
+        ..default_instantiate_msg()
+      };
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      // a 10% move is within the limit
+      let msg = ExecuteMsg::UpdatePrice { pair: lemon_luna(), price: Decimal::percent(1100) };
+      let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+      assert!(res.attributes.contains(&attr("status", "active")));
+
+      // a fat-fingered price trips the breaker and the last accepted price is reported as paused
+      let msg = ExecuteMsg::UpdatePrice { pair: lemon_luna(), price: Decimal::percent(110_000) };
+      let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+      assert!(res.attributes.contains(&attr("circuit_breaker", "max_update_change")));
+
+      let res = query(deps.as_ref(), mock_env(), QueryMsg::QueryPrice { pair: lemon_luna() }).unwrap();
+      let price_response: PriceResponse = from_binary(&res).unwrap();
+      assert_eq!(Decimal::percent(1100), price_response.price);
+      assert_eq!(PriceStatus::Paused, price_response.status);
+      let res: PausedFeedResponse = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::PausedFeed { pair: lemon_luna() }).unwrap()).unwrap();
+      let paused_at = mock_env().block.time.seconds();
+      assert_eq!(Some(PausedFeed { rejected_price: Decimal::percent(110_000), paused_at }), res.paused);
+
+      // further updates are held back while paused
+      let msg = ExecuteMsg::UpdatePrice { pair: lemon_luna(), price: Decimal::percent(1150) };
+      let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+      assert!(res.attributes.contains(&attr("status", "paused")));
+
+      let msg = ExecuteMsg::ConfirmPrice { pair: lemon_luna() };
+      let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), msg.clone()).unwrap_err();
+      assert!(matches!(err, ContractError::Unauthorized {}));
+      let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg.clone()).unwrap();
+      let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap_err();
+      assert!(matches!(err, ContractError::FeedNotPaused {}));
+
+      let res = query(deps.as_ref(), mock_env(), QueryMsg::QueryPrice { pair: lemon_luna() }).unwrap();
+      let price_response: PriceResponse = from_binary(&res).unwrap();
+      assert_eq!(Decimal::percent(1150), price_response.price);
+      assert_eq!(PriceStatus::Active, price_response.status);
+      let res: PausedFeedResponse = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::PausedFeed { pair: lemon_luna() }).unwrap()).unwrap();
+      assert_eq!(None, res.paused);
+    }
+
+    #[test]
+    fn circuit_breaker_pauses_feed_on_huge_jump() {
+      let mut deps = mock_dependencies(&[]);
+
+      let msg = InstantiateMsg {
+        prices: vec![PairPrice { pair: lemon_luna(), price: Decimal::from_ratio(1u128, 1_000_000_000_000_000_000u128) }],
+        circuit_breaker: Some(CircuitBreaker { max_update_change: Some(Decimal::percent(10)), max_window_change: None, window: 0 }),
+        ..default_instantiate_msg()
+      };
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      // the change is too large to fit in a Decimal and must still pause the feed
+      let msg = ExecuteMsg::UpdatePrice { pair: lemon_luna(), price: Decimal::percent(100_000) };
+      let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+      assert!(res.attributes.contains(&attr("circuit_breaker", "max_update_change")));
+
+      let res = query(deps.as_ref(), mock_env(), QueryMsg::QueryPrice { pair: lemon_luna() }).unwrap();
       let price_response: PriceResponse = from_binary(&res).unwrap();
-      assert_eq!(18, price_response.price);
+      assert_eq!(PriceStatus::Paused, price_response.status);
+    }
+
+    #[test]
+    fn circuit_breaker_limits_change_over_window() {
+      let mut deps = mock_dependencies(&[]);
+      let mut env = mock_env();
+
+      let msg = default_instantiate_msg();
+      let _res = instantiate(deps.as_mut(), env.clone(), mock_info("creator", &[]), msg).unwrap();
+
+      let circuit_breaker = CircuitBreaker { max_update_change: Some(Decimal::percent(10)), max_window_change: Some(Decimal::percent(15)), window: 60 };
+      let msg = ExecuteMsg::UpdateCircuitBreaker { circuit_breaker };
+      let err = execute(deps.as_mut(), env.clone(), mock_info("anyone", &[]), msg.clone()).unwrap_err();
+      assert!(matches!(err, ContractError::Unauthorized {}));
+      let _res = execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), msg).unwrap();
+
+      // two 10% steps stay within the per-update limit but add up to 21% within the window
+      env.block.time = env.block.time.plus_seconds(10);
+      let msg = ExecuteMsg::UpdatePrice { pair: lemon_luna(), price: Decimal::percent(1100) };
+      let res = execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), msg).unwrap();
+      assert!(res.attributes.contains(&attr("status", "active")));
+
+      env.block.time = env.block.time.plus_seconds(10);
+      let msg = ExecuteMsg::UpdatePrice { pair: lemon_luna(), price: Decimal::percent(1210) };
+      let res = execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), msg).unwrap();
+      assert!(res.attributes.contains(&attr("circuit_breaker", "max_window_change")));
+
+      let msg = QueryMsg::AllPrices { start_after: None, limit: None };
+      let res: AllPricesResponse = from_binary(&query(deps.as_ref(), env, msg).unwrap()).unwrap();
+      let price = res.prices[0].price.as_ref().unwrap();
+      assert_eq!(Decimal::percent(1100), price.price);
+      assert_eq!(PriceStatus::Paused, price.status);
     }
 }
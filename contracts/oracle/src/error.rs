@@ -9,10 +9,33 @@ pub enum ContractError {
     #[error("Unauthorized")]
     Unauthorized {},
 
-    #[error("Price has to be positive.")]
-    PriceInstantiationError {}, // Add any other custom errors you like here.
-                                // Look at https://docs.rs/thiserror/1.0.21/thiserror/ for details.
+    #[error("Price has to be positive and within the configured bounds.")]
+    PriceInstantiationError {},
 
-    #[error("Price cannot be updated.")]
-    PriceUpdateError {}, // Add any other custom errors you like here.
+    #[error("Price cannot be updated: it has to be positive and within the configured bounds.")]
+    PriceUpdateError {},
+
+    #[error("Minimum price cannot exceed the maximum price")]
+    InvalidPriceBounds {},
+
+    #[error("Publisher already exists")]
+    PublisherAlreadyExists {},
+
+    #[error("Publisher not found")]
+    PublisherNotFound {},
+
+    #[error("Price feed is not paused")]
+    FeedNotPaused {},
+
+    #[error("No ownership transfer is pending")]
+    NoPendingOwner {},
+
+    #[error("Already subscribed or waiting for approval")]
+    AlreadySubscribed {},
+
+    #[error("No subscription request from this address")]
+    SubscriptionNotRequested {},
+
+    #[error("Subscriber not found")]
+    SubscriberNotFound {},
 }
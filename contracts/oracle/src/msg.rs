@@ -1,26 +1,144 @@
+use cosmwasm_std::{Addr, Decimal};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+pub use crate::state::{CircuitBreaker, PausedFeed};
+pub use shared::oracle::{CallbackMsg, Pair, PriceResponse, PriceStatus};
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InstantiateMsg {
-    pub price: u64,
+    // initial prices, submitted on behalf of the instantiator
+    pub prices: Vec<PairPrice>,
+    // additional publishers besides the instantiator
+    pub publishers: Option<Vec<String>>,
+    pub max_submission_age: Option<u64>,
+    pub max_history_length: Option<u32>,
+    pub circuit_breaker: Option<CircuitBreaker>,
+    pub min_price: Option<Decimal>,
+    pub max_price: Option<Decimal>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PairPrice {
+    pub pair: Pair,
+    pub price: Decimal,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ExecuteMsg {
-    UpdatePrice { price: u64 },
+    UpdatePrice { pair: Pair, price: Decimal },
+    AddPublisher { address: String },
+    RemovePublisher { address: String },
+    UpdateCircuitBreaker { circuit_breaker: CircuitBreaker },
+    UpdatePriceBounds { min_price: Option<Decimal>, max_price: Option<Decimal> },
+    // ConfirmPrice accepts the current aggregated price of a paused pair and resumes the feed
+    ConfirmPrice { pair: Pair },
+    // ProposeNewOwner starts an ownership transfer, which the new owner completes with AcceptOwnership
+    ProposeNewOwner { owner: String },
+    AcceptOwnership {},
+    // Subscribe asks for the sender to receive a CallbackMsg with every published price, once the owner approves
+    Subscribe {},
+    ApproveSubscriber { address: String },
+    // RemoveSubscriber can be called by the owner or by the subscriber itself
+    RemoveSubscriber { address: String },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
-    // GetCount returns the price in LUNA as a json-encoded number
-    QueryPrice {},
+    // QueryPrice returns the median of fresh publisher prices for the pair as a json-encoded decimal string,
+    // or the last accepted price with a paused status while the pair's circuit breaker is tripped
+    QueryPrice { pair: Pair },
+    // AllPrices returns the current price of every known pair, ordered by pair key
+    AllPrices { start_after: Option<Pair>, limit: Option<u32> },
+    QueryPublishers { pair: Pair },
+    // PriceHistory returns aggregated price observations in ascending time order
+    PriceHistory { pair: Pair, start_after: Option<u64>, limit: Option<u32> },
+    // Twap returns the time-weighted average price over the last `window` seconds
+    Twap { pair: Pair, window: u64 },
+    QueryConfig {},
+    QuerySubscribers {},
+    // PausedFeed returns the price that tripped the pair's circuit breaker and when, if the feed is paused
+    PausedFeed { pair: Pair },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
-pub struct PriceResponse {
-  pub price: u64
-}
\ No newline at end of file
+pub struct ConfigResponse {
+  pub owner: Addr,
+  pub pending_owner: Option<Addr>,
+  pub publishers: Vec<Addr>,
+  pub max_submission_age: u64,
+  pub max_history_length: u32,
+  pub circuit_breaker: CircuitBreaker,
+  pub min_price: Option<Decimal>,
+  pub max_price: Option<Decimal>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct SubscribersResponse {
+  pub subscribers: Vec<Addr>,
+  // subscriptions waiting for the owner's approval
+  pub requests: Vec<Addr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PairPriceResponse {
+  pub pair: Pair,
+  // None when no publisher has a fresh price for the pair
+  pub price: Option<PriceResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct AllPricesResponse {
+  pub prices: Vec<PairPriceResponse>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PublisherInfo {
+  pub address: Addr,
+  pub price: Option<Decimal>,
+  pub last_updated_height: Option<u64>,
+  pub last_updated_time: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PublishersResponse {
+  pub publishers: Vec<PublisherInfo>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PriceObservationResponse {
+  pub time: u64,
+  pub height: u64,
+  pub price: Decimal,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PriceHistoryResponse {
+  pub observations: Vec<PriceObservationResponse>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct TwapResponse {
+  pub price: Decimal,
+  // start of the period actually covered by observations, which can be later than now - window
+  pub start_time: u64,
+  pub end_time: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PausedFeedResponse {
+  // None while the feed is active
+  pub paused: Option<PausedFeed>,
+}
@@ -1,13 +1,77 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::Addr;
-use cw_storage_plus::Item;
+use cosmwasm_std::{Addr, Decimal, Empty};
+use cw_storage_plus::{Item, Map, U64Key};
+use shared::oracle::Pair;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct State {
-    pub price: u64,
     pub owner: Addr,
+    // proposed owner, who has to accept before ownership moves
+    pub pending_owner: Option<Addr>,
+    // submissions older than this (in seconds) are left out of the median
+    pub max_submission_age: u64,
+    // number of aggregated price observations kept in the history of each pair
+    pub max_history_length: u32,
+    pub circuit_breaker: CircuitBreaker,
+    // submitted prices outside these bounds are rejected
+    pub min_price: Option<Decimal>,
+    pub max_price: Option<Decimal>,
+}
+
+// Limits on how far the aggregated price may move before the feed is paused. Changes are
+// relative to the previous price, e.g. 0.1 for 10%.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct CircuitBreaker {
+    pub max_update_change: Option<Decimal>,
+    pub max_window_change: Option<Decimal>,
+    // length of the window (in seconds) that `max_window_change` applies to
+    pub window: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PausedFeed {
+    // the aggregated price that tripped the circuit breaker
+    pub rejected_price: Decimal,
+    pub paused_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PriceSubmission {
+    pub price: Decimal,
+    pub last_updated_height: u64,
+    pub last_updated_time: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PriceObservation {
+    pub price: Decimal,
+    pub height: u64,
 }
 
 pub const STATE: Item<State> = Item::new("state");
+
+// approved publishers
+pub const PUBLISHERS: Map<&Addr, Empty> = Map::new("publishers");
+
+// every pair that has been priced, keyed by `Pair::key`
+pub const PAIRS: Map<&str, Pair> = Map::new("pairs");
+
+// the last price each publisher submitted for a pair
+pub const SUBMISSIONS: Map<(&str, &Addr), PriceSubmission> = Map::new("submissions");
+
+// aggregated price of a pair after each update, keyed by block time (in seconds)
+pub const PRICE_HISTORY: Map<(&str, U64Key), PriceObservation> = Map::new("price_history");
+
+// approved subscriber contracts, mapped to the id their callbacks reply with
+pub const SUBSCRIBERS: Map<&Addr, u64> = Map::new("subscribers");
+
+// contracts waiting for the owner to approve their subscription
+pub const SUBSCRIPTION_REQUESTS: Map<&Addr, Empty> = Map::new("subscription_requests");
+
+// last id handed out to a subscriber
+pub const SUBSCRIBER_COUNT: Item<u64> = Item::new("subscriber_count");
+
+// pairs whose feed is paused until the owner confirms the price, keyed by `Pair::key`
+pub const PAUSED: Map<&str, PausedFeed> = Map::new("paused");
@@ -0,0 +1,209 @@
+#[cfg(not(feature = "library"))]
+use cosmwasm_std::entry_point;
+use cosmwasm_std::{
+    Binary, Deps, DepsMut, Env, MessageInfo, Response, StdError, StdResult, to_binary,
+};
+use cw2::set_contract_version;
+use cw_asset::AssetInfo;
+
+use crate::error::ContractError;
+use crate::msg::{ConfigResponse, ExecuteMsg, InstantiateMsg, Pair, PriceResponse, PriceStatus, QueryMsg};
+use crate::state::{STATE, State};
+use shared::math::decimal_mul;
+use shared::querier::query_exchange_rate;
+use shared::oracle::QueryMsg as OracleQueryMsg;
+
+// version info for migration info
+const CONTRACT_NAME: &str = "crates.io:oracle-proxy";
+const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+const DEFAULT_CW20_QUOTE_DENOM: &str = "uluna";
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn instantiate(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    msg: InstantiateMsg,
+) -> Result<Response, ContractError> {
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    let initial_state = State {
+      owner: info.sender,
+      oracle_address: msg.oracle_address.map(|address| deps.api.addr_validate(&address)).transpose()?,
+      cw20_quote_denom: msg.cw20_quote_denom.unwrap_or_else(|| DEFAULT_CW20_QUOTE_DENOM.to_string()),
+    };
+
+    STATE.save(deps.storage, &initial_state)?;
+
+    Ok(Response::new().add_attribute("owner", initial_state.owner))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn execute(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    match msg {
+      ExecuteMsg::UpdateConfig { oracle_address, cw20_quote_denom } => {
+        try_update_config(deps, info, oracle_address, cw20_quote_denom)
+      }
+    }
+}
+
+pub fn try_update_config(
+  deps: DepsMut,
+  info: MessageInfo,
+  oracle_address: Option<String>,
+  cw20_quote_denom: Option<String>,
+) -> Result<Response, ContractError> {
+  let mut state = STATE.load(deps.storage)?;
+  if info.sender != state.owner {
+    return Err(ContractError::Unauthorized {});
+  }
+
+  if let Some(oracle_address) = oracle_address {
+    state.oracle_address = Some(deps.api.addr_validate(&oracle_address)?);
+  }
+  if let Some(cw20_quote_denom) = cw20_quote_denom {
+    state.cw20_quote_denom = cw20_quote_denom;
+  }
+  STATE.save(deps.storage, &state)?;
+
+  Ok(Response::new().add_attribute("method", "try_update_config"))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+      QueryMsg::QueryPrice { pair } => to_binary(&query_price(deps, env, pair)?),
+      QueryMsg::QueryConfig {} => to_binary(&query_config(deps)?),
+    }
+}
+
+fn query_price(deps: Deps, env: Env, pair: Pair) -> StdResult<PriceResponse> {
+  let quote_denom = match pair.quote.check(deps.api)? {
+    AssetInfo::Native(denom) => denom,
+    AssetInfo::Cw20(_) => return Err(StdError::generic_err("Prices can only be quoted in native denoms")),
+  };
+
+  match pair.base.check(deps.api)? {
+    // market rates are set every block
+    AssetInfo::Native(base_denom) => Ok(PriceResponse {
+      price: query_exchange_rate(&deps.querier, &base_denom, &quote_denom)?,
+      last_updated_height: env.block.height,
+      last_updated_time: env.block.time.seconds(),
+      status: PriceStatus::Active,
+    }),
+    AssetInfo::Cw20(token) => {
+      let state = STATE.load(deps.storage)?;
+      let oracle_address = state
+        .oracle_address
+        .ok_or_else(|| StdError::generic_err("No oracle is configured for CW20 prices"))?;
+
+      // the oracle's update time and status carry over to the converted price
+      let oracle_pair = Pair::new(AssetInfo::Cw20(token), AssetInfo::native(state.cw20_quote_denom.as_str()));
+      let oracle_price: PriceResponse = deps
+        .querier
+        .query_wasm_smart(oracle_address, &OracleQueryMsg::QueryPrice { pair: oracle_pair })?;
+      let exchange_rate = query_exchange_rate(&deps.querier, &state.cw20_quote_denom, &quote_denom)?;
+
+      Ok(PriceResponse {
+        price: decimal_mul(oracle_price.price, exchange_rate)?,
+        ..oracle_price
+      })
+    }
+  }
+}
+
+fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
+  let state = STATE.load(deps.storage)?;
+  Ok(ConfigResponse {
+    owner: state.owner,
+    oracle_address: state.oracle_address,
+    cw20_quote_denom: state.cw20_quote_denom,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::{mock_env, mock_info};
+    use cosmwasm_std::{from_binary, Addr, Decimal};
+    use testing::mock_querier::mock_dependencies;
+
+    const TOKEN: &str = "hyp0000";
+    const ORACLE: &str = "oracle000";
+
+    fn token_ust() -> Pair {
+      Pair::new(AssetInfo::cw20(Addr::unchecked(TOKEN)), AssetInfo::native("uusd"))
+    }
+
+    #[test]
+    fn native_prices_come_from_market_rates() {
+      let mut deps = mock_dependencies(&[]);
+      deps.querier.with_exchange_rates(&[("uusd", Decimal::percent(9000)), ("ukrw", Decimal::percent(10_800_000))]);
+
+      let msg = InstantiateMsg { oracle_address: None, cw20_quote_denom: None };
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      let pair = Pair::new(AssetInfo::native("uluna"), AssetInfo::native("uusd"));
+      let res: PriceResponse = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::QueryPrice { pair }).unwrap()).unwrap();
+      assert_eq!(Decimal::percent(9000), res.price);
+      assert_eq!(mock_env().block.time.seconds(), res.last_updated_time);
+      assert_eq!(PriceStatus::Active, res.status);
+
+      // cross rates go through luna
+      let pair = Pair::new(AssetInfo::native("uusd"), AssetInfo::native("ukrw"));
+      let res: PriceResponse = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::QueryPrice { pair }).unwrap()).unwrap();
+      assert_eq!(Decimal::percent(120_000), res.price);
+
+      let pair = Pair::new(AssetInfo::native("uluna"), AssetInfo::native("ueur"));
+      assert!(query(deps.as_ref(), mock_env(), QueryMsg::QueryPrice { pair }).is_err());
+
+      // tokens need an oracle
+      assert!(query(deps.as_ref(), mock_env(), QueryMsg::QueryPrice { pair: token_ust() }).is_err());
+    }
+
+    #[test]
+    fn cw20_prices_are_converted_at_market_rates() {
+      let mut deps = mock_dependencies(&[]);
+      deps.querier.with_exchange_rates(&[("uusd", Decimal::percent(9000))]);
+      deps.querier.with_oracle_price_updated_at(Decimal::percent(150), 12_000, 1_600_000_000);
+
+      let msg = InstantiateMsg { oracle_address: Some(String::from(ORACLE)), cw20_quote_denom: None };
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      // 1.5 luna at 90 ust per luna
+      let res: PriceResponse = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::QueryPrice { pair: token_ust() }).unwrap()).unwrap();
+      assert_eq!(Decimal::percent(13_500), res.price);
+      assert_eq!(12_000, res.last_updated_height);
+      assert_eq!(1_600_000_000, res.last_updated_time);
+
+      // the swap contracts' pair passes straight through
+      let pair = Pair::new(AssetInfo::cw20(Addr::unchecked(TOKEN)), AssetInfo::native("uluna"));
+      let res: PriceResponse = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::QueryPrice { pair }).unwrap()).unwrap();
+      assert_eq!(Decimal::percent(150), res.price);
+
+      let pair = Pair::new(AssetInfo::native("uluna"), AssetInfo::cw20(Addr::unchecked(TOKEN)));
+      assert!(query(deps.as_ref(), mock_env(), QueryMsg::QueryPrice { pair }).is_err());
+    }
+
+    #[test]
+    fn update_config() {
+      let mut deps = mock_dependencies(&[]);
+
+      let msg = InstantiateMsg { oracle_address: None, cw20_quote_denom: None };
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      let msg = ExecuteMsg::UpdateConfig { oracle_address: Some(String::from(ORACLE)), cw20_quote_denom: Some(String::from("uusd")) };
+      let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), msg.clone()).unwrap_err();
+      assert!(matches!(err, ContractError::Unauthorized {}));
+      let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      let res: ConfigResponse = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::QueryConfig {}).unwrap()).unwrap();
+      assert_eq!(Some(Addr::unchecked(ORACLE)), res.oracle_address);
+      assert_eq!("uusd", res.cw20_quote_denom);
+    }
+}
@@ -0,0 +1,35 @@
+use cosmwasm_std::Addr;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+pub use shared::oracle::{Pair, PriceResponse, PriceStatus};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InstantiateMsg {
+    pub oracle_address: Option<String>,
+    // defaults to uluna
+    pub cw20_quote_denom: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    UpdateConfig { oracle_address: Option<String>, cw20_quote_denom: Option<String> },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    // QueryPrice answers like the oracle contract: native pairs are priced from the Terra market module,
+    // CW20 tokens from the configured oracle and converted into the quote denom at market rates
+    QueryPrice { pair: Pair },
+    QueryConfig {},
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ConfigResponse {
+  pub owner: Addr,
+  pub oracle_address: Option<Addr>,
+  pub cw20_quote_denom: String,
+}
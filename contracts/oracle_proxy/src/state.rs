@@ -0,0 +1,16 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::Addr;
+use cw_storage_plus::Item;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct State {
+    pub owner: Addr,
+    // oracle contract that prices CW20 tokens, if any
+    pub oracle_address: Option<Addr>,
+    // native denom CW20 tokens are priced in at the oracle, converted to the requested quote at market rates
+    pub cw20_quote_denom: String,
+}
+
+pub const STATE: Item<State> = Item::new("state");
@@ -3,7 +3,11 @@ use std::fs::create_dir_all;
 
 use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
 
-use swap::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
+use swap::msg::{
+    AcceptedDenomsResponse, AllowListResponse, AllowanceResponse, ConfigResponse, CurveResponse,
+    Cw20HookMsg, ExecuteMsg, FeesResponse, InstantiateMsg, InventoryResponse, QueryMsg,
+    SimulateResponse, SimulateSellResponse, TreasuryResponse, VestingStatusResponse,
+};
 fn main() {
     let mut out_dir = current_dir().unwrap();
     out_dir.push("schema");
@@ -13,4 +17,16 @@ fn main() {
     export_schema(&schema_for!(InstantiateMsg), &out_dir);
     export_schema(&schema_for!(ExecuteMsg), &out_dir);
     export_schema(&schema_for!(QueryMsg), &out_dir);
+    export_schema(&schema_for!(Cw20HookMsg), &out_dir);
+    export_schema(&schema_for!(ConfigResponse), &out_dir);
+    export_schema(&schema_for!(SimulateResponse), &out_dir);
+    export_schema(&schema_for!(SimulateSellResponse), &out_dir);
+    export_schema(&schema_for!(CurveResponse), &out_dir);
+    export_schema(&schema_for!(VestingStatusResponse), &out_dir);
+    export_schema(&schema_for!(InventoryResponse), &out_dir);
+    export_schema(&schema_for!(TreasuryResponse), &out_dir);
+    export_schema(&schema_for!(FeesResponse), &out_dir);
+    export_schema(&schema_for!(AllowanceResponse), &out_dir);
+    export_schema(&schema_for!(AllowListResponse), &out_dir);
+    export_schema(&schema_for!(AcceptedDenomsResponse), &out_dir);
 }
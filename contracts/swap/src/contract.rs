@@ -1,22 +1,36 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    Binary, Deps, DepsMut, Empty, Env, MessageInfo, Response, StdError,
-    StdResult, attr, to_binary, Addr, Uint128,
-    CosmosMsg, WasmMsg, BankMsg, coin
+    Binary, Decimal, Deps, DepsMut, Empty, Env, MessageInfo, Response,
+    StdError, StdResult, attr, from_binary, to_binary, Addr, Uint128,
+    CosmosMsg, WasmMsg, BankMsg, Coin, Order, coin
 };
 
-use cw20::{BalanceResponse, Cw20ExecuteMsg};
+use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg};
 use cw2::set_contract_version;
+use cw_storage_plus::U64Key;
+use cw_asset::{Asset, AssetInfo, AssetUnchecked};
 
+use crate::curve::Curve;
 use crate::error::ContractError;
-use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
-use crate::state::{STATE, State};
-use shared::oracle::PriceResponse;
+use crate::msg::{
+    AcceptedDenomsResponse, AllowListEntry, AllowListResponse, AllowanceResponse, ConfigResponse,
+    CurveResponse, Cw20HookMsg, ExecuteMsg, FeesResponse, InstantiateMsg, InventoryResponse, QueryMsg,
+    SaleLimits, SimulateResponse, SimulateSellResponse, Tier, TreasuryResponse, Vesting,
+    VestingStatusResponse,
+};
+use crate::state::{
+    ACCUMULATED_FEES, ALLOW_LIST, CURVE_SUPPLY, PURCHASES, STATE, State, TOKENS_LOCKED, TOKENS_SOLD,
+    VESTING_COUNT, VESTING_RECORDS, VestingRecord,
+};
+use shared::math::mul_ceil;
+use shared::querier::{query_all_balances, query_exchange_rate, query_token_balance};
+use shared::oracle::{Pair, PriceResponse, PriceStatus, QueryMsg as OracleQueryMsg};
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:swap";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+const MAX_FEE_BPS: u16 = 10_000;
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
@@ -28,12 +42,41 @@ pub fn instantiate(
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
     let initial_state = State {
-      owner: info.sender,
+      owner: info.sender.clone(),
       token_address: msg.token_address,
       oracle_address: msg.oracle_address,
+      max_price_age: msg.max_price_age,
+      spread: msg.spread.unwrap_or_else(Decimal::zero),
+      fee_bps: msg.fee_bps.unwrap_or_default(),
+      fee_recipient: match msg.fee_recipient {
+        Some(fee_recipient) => deps.api.addr_validate(fee_recipient.as_str())?,
+        None => info.sender,
+      },
+      accepted_denoms: msg.accepted_denoms.unwrap_or_default(),
+      sale_limits: msg.sale_limits.unwrap_or_default(),
+      public_sale_time: msg.public_sale_time,
+      curve: msg.curve,
+      vesting: msg.vesting,
     };
+    if initial_state.spread >= Decimal::one() {
+      return Err(ContractError::InvalidSpread {});
+    }
+    if initial_state.fee_bps >= MAX_FEE_BPS {
+      return Err(ContractError::InvalidFee {});
+    }
+    validate_sale_limits(&initial_state.sale_limits)?;
+    match &initial_state.curve {
+      Some(curve) if !curve.is_valid() => return Err(ContractError::InvalidCurve {}),
+      None if initial_state.oracle_address.is_none() => return Err(ContractError::NoPriceSource {}),
+      _ => {}
+    }
+    validate_vesting(&initial_state.vesting)?;
 
     STATE.save(deps.storage, &initial_state)?;
+    TOKENS_SOLD.save(deps.storage, &Uint128::zero())?;
+    CURVE_SUPPLY.save(deps.storage, &Uint128::zero())?;
+    TOKENS_LOCKED.save(deps.storage, &Uint128::zero())?;
+    VESTING_COUNT.save(deps.storage, &0)?;
 
     Ok(Response::new().add_attributes(vec![
         attr("owner", initial_state.owner),
@@ -49,90 +92,730 @@ pub fn execute(
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
     match msg {
-      ExecuteMsg::Buy {} => return try_buy(deps, env, info, msg),
-      ExecuteMsg::Withdraw { amount } => return try_withdraw(deps, env, info, amount)
+      ExecuteMsg::Buy { min_tokens_out, max_price, deadline } => {
+        try_buy(deps, env, info, min_tokens_out, max_price, deadline)
+      }
+      ExecuteMsg::Withdraw { assets, recipient } => try_withdraw(deps, env, info, assets, recipient),
+      ExecuteMsg::WithdrawAll { recipient } => try_withdraw_all(deps, env, info, recipient),
+      ExecuteMsg::Receive(cw20_msg) => try_receive(deps, env, info, cw20_msg),
+      ExecuteMsg::UpdateSpread { spread } => try_update_spread(deps, info, spread),
+      ExecuteMsg::UpdateFeeConfig { fee_bps, fee_recipient } => {
+        try_update_fee_config(deps, info, fee_bps, fee_recipient)
+      }
+      ExecuteMsg::WithdrawFees {} => try_withdraw_fees(deps, info),
+      ExecuteMsg::UpdateAcceptedDenoms { denoms } => try_update_accepted_denoms(deps, info, denoms),
+      ExecuteMsg::UpdateSaleLimits { sale_limits } => try_update_sale_limits(deps, info, sale_limits),
+      ExecuteMsg::UpdatePresale { public_sale_time } => try_update_presale(deps, info, public_sale_time),
+      ExecuteMsg::UpdateAllowList { add, remove } => try_update_allow_list(deps, info, add, remove),
+      ExecuteMsg::UpdateVesting { vesting } => try_update_vesting(deps, info, vesting),
+      ExecuteMsg::Claim {} => try_claim(deps, env, info),
+    }
+}
+
+pub fn try_buy(
+  deps: DepsMut,
+  env: Env,
+  info: MessageInfo,
+  min_tokens_out: Option<Uint128>,
+  max_price: Option<Decimal>,
+  deadline: Option<u64>,
+) -> Result<Response, ContractError> {
+  if let Some(deadline) = deadline {
+    if env.block.time.seconds() > deadline {
+      return Err(ContractError::DeadlineExceeded { deadline });
+    }
+  }
+
+  let state = STATE.load(deps.storage)?;
+  let quote = quote_buy(deps.as_ref(), &env, &state, Some(&info.sender), &info.funds, max_price)?;
+  let coins_to_be_sent = quote.tokens;
+
+  if let Some(min_tokens_out) = min_tokens_out {
+    if coins_to_be_sent < min_tokens_out {
+      return Err(ContractError::MinTokensOutNotMet { tokens_out: coins_to_be_sent, min_tokens_out });
+    }
+  }
+
+  let mut fees: Vec<Coin> = vec![];
+  let mut refunds: Vec<Coin> = vec![];
+  let mut attributes = vec![];
+  for (paid, purchase) in info.funds.iter().zip(quote.purchases) {
+    attributes.extend(vec![
+      attr("gross", paid.to_string()),
+      attr("fee", purchase.fee.to_string()),
+      attr("net", coin(purchase.net.u128(), &paid.denom).to_string()),
+      attr("refunded", purchase.refund.to_string()),
+    ]);
+    if !purchase.fee.amount.is_zero() {
+      fees.push(purchase.fee);
+    }
+    if !purchase.refund.amount.is_zero() {
+      refunds.push(purchase.refund);
+    }
+  }
+
+  PURCHASES.save(deps.storage, &info.sender, &quote.purchased.checked_add(coins_to_be_sent).map_err(StdError::from)?)?;
+  TOKENS_SOLD.save(deps.storage, &quote.tokens_sold.checked_add(coins_to_be_sent).map_err(StdError::from)?)?;
+  if state.curve.is_some() {
+    CURVE_SUPPLY.save(deps.storage, &quote.supply)?;
+  }
+  for fee in fees {
+    ACCUMULATED_FEES.update(deps.storage, &fee.denom, |fees| -> StdResult<_> {
+      Ok(fees.unwrap_or_default().checked_add(fee.amount)?)
+    })?;
+  }
+
+  let mut response = Response::new()
+    .add_attributes(vec![
+      attr("price", quote.price.to_string()),
+      attr("coins_sent", coins_to_be_sent.to_string()),
+    ])
+    .add_attributes(attributes);
+
+  // vesting tokens stay in the contract until the buyer claims them
+  match state.vesting {
+    Some(vesting) => {
+      let now = env.block.time.seconds();
+      let record = VestingRecord {
+        amount: coins_to_be_sent,
+        claimed: Uint128::zero(),
+        start_time: now,
+        cliff_time: now.saturating_add(vesting.cliff),
+        end_time: now.saturating_add(vesting.duration),
+      };
+      let id = VESTING_COUNT.load(deps.storage)? + 1;
+      VESTING_COUNT.save(deps.storage, &id)?;
+      VESTING_RECORDS.save(deps.storage, (&info.sender, U64Key::new(id)), &record)?;
+      TOKENS_LOCKED.update(deps.storage, |locked| -> StdResult<_> { Ok(locked.checked_add(coins_to_be_sent)?) })?;
+      response = response.add_attribute("vesting_id", id.to_string());
     }
+    None => {
+      response = response.add_message(transfer_tokens_msg(&state.token_address, &info.sender, coins_to_be_sent)?);
+    }
+  }
+
+  if !refunds.is_empty() {
+    response = response.add_message(CosmosMsg::Bank(BankMsg::Send {
+      to_address: info.sender.to_string(),
+      amount: refunds,
+    }));
+  }
+
+  Ok(response)
 }
 
-pub fn try_buy(deps: DepsMut, env: Env, info: MessageInfo, _msg: ExecuteMsg) -> Result<Response, ContractError> {
-  let price_in_luna = get_price(deps.as_ref())?.price as u128;
+struct BuyQuote {
+  price: Decimal,
+  tokens: Uint128,
+  // one per coin paid, in the same order
+  purchases: Vec<Purchase>,
+  // the curve supply once the tokens are sold
+  supply: Uint128,
+  // tokens bought by the buyer and by everyone before this buy
+  purchased: Uint128,
+  tokens_sold: Uint128,
+}
 
-  if info.funds.len() == 0 {
+// Prices `funds` for `buyer` and runs every check Buy does, without saving anything. Without a
+// buyer the funds are quoted as a first purchase at the public price.
+fn quote_buy(
+  deps: Deps,
+  env: &Env,
+  state: &State,
+  buyer: Option<&Addr>,
+  funds: &[Coin],
+  max_price: Option<Decimal>,
+) -> Result<BuyQuote, ContractError> {
+  let now = env.block.time.seconds();
+  if let Some(start_time) = state.sale_limits.start_time {
+    if now < start_time {
+      return Err(ContractError::SaleNotStarted { start_time });
+    }
+  }
+  if let Some(end_time) = state.sale_limits.end_time {
+    if now >= end_time {
+      return Err(ContractError::SaleEnded { end_time });
+    }
+  }
+
+  let tier = match buyer {
+    Some(buyer) => presale_tier(deps, state, env, buyer)?,
+    None => None,
+  };
+  let curve = purchase_curve(deps, env, state, tier.as_ref())?;
+  let mut supply = CURVE_SUPPLY.load(deps.storage)?;
+  let price_in_luna = curve.spot_price(supply)?;
+  if price_in_luna.is_zero() {
+    return Err(ContractError::InvalidPrice {});
+  }
+  if let Some(max_price) = max_price {
+    if price_in_luna > max_price {
+      return Err(ContractError::MaxPriceExceeded { price: price_in_luna, max_price });
+    }
+  }
+
+  if funds.is_empty() {
       return Err(ContractError::CoinMismatch {})
   }
 
-  let luna_received: Uint128 = info
-    .funds
-    .iter()
-    .find(|c| c.denom == String::from("uluna"))
-    .map(|c| Uint128::from(c.amount))
-    .unwrap_or_else(Uint128::zero);
+  let mut coins_to_be_sent = Uint128::zero();
+  let mut purchases = vec![];
 
-  let coins_to_be_sent = luna_received.u128() / price_in_luna;
-  let coins_in_contract = get_balance_of_cw20(deps.as_ref(), env.contract.address)?.balance.u128();
+  // each coin buys tokens on its own, along the curve converted to its denom
+  for paid in funds {
+    let purchase = quote_purchase(deps, state, &curve, supply, paid)?;
+    if purchase.tokens.is_zero() {
+      return Err(ContractError::InvalidQuantity {});
+    }
+    supply = supply.checked_add(purchase.tokens).map_err(StdError::from)?;
+    coins_to_be_sent = coins_to_be_sent.checked_add(purchase.tokens).map_err(StdError::from)?;
+    purchases.push(purchase);
+  }
+
+  let purchased = match buyer {
+    Some(buyer) => PURCHASES.may_load(deps.storage, buyer)?.unwrap_or_default(),
+    None => Uint128::zero(),
+  };
+  if let Some(max_tokens) = state.sale_limits.max_tokens_per_buyer {
+    let remaining = max_tokens.saturating_sub(purchased);
+    if coins_to_be_sent > remaining {
+      return Err(ContractError::BuyerLimitExceeded { requested: coins_to_be_sent, remaining });
+    }
+  }
+  if let Some(max_tokens) = tier.and_then(|tier| tier.max_tokens) {
+    let remaining = max_tokens.saturating_sub(purchased);
+    if coins_to_be_sent > remaining {
+      return Err(ContractError::TierLimitExceeded { requested: coins_to_be_sent, remaining });
+    }
+  }
+  let tokens_sold = TOKENS_SOLD.load(deps.storage)?;
+  if let Some(max_tokens) = state.sale_limits.max_tokens_sold {
+    let remaining = max_tokens.saturating_sub(tokens_sold);
+    if coins_to_be_sent > remaining {
+      return Err(ContractError::SaleCapExceeded { requested: coins_to_be_sent, remaining });
+    }
+  }
+
+  let coins_in_contract = tokens_for_sale(deps, env, &state.token_address)?;
 
   if coins_in_contract < coins_to_be_sent { return Err(ContractError::InsufficientCoinsInContract {}) }
 
-  let token_addr = STATE.load(deps.storage)?.token_address;
-  let msg_execute = Cw20ExecuteMsg::Transfer {
-      recipient: info.sender.to_string(),
-      amount: Uint128::from(coins_to_be_sent),
-  };
+  Ok(BuyQuote { price: price_in_luna, tokens: coins_to_be_sent, purchases, supply, purchased, tokens_sold })
+}
 
-  Ok(Response::new().add_attributes(
-    vec![
-        ("price", price_in_luna.to_string()),
-        ("luna_received", luna_received.to_string()),
-        ("coins_sent", coins_to_be_sent.to_string()),
-      ]
-    ).add_message(CosmosMsg::Wasm(WasmMsg::Execute {
-        contract_addr: token_addr.to_string(),
-        msg: to_binary(&msg_execute)?,
-        funds: vec![],
-    }))
-  )
-}
-
-pub fn try_withdraw(deps: DepsMut, env: Env, info: MessageInfo, amount: i32) -> Result<Response, ContractError> {
-  let owner = STATE.load(deps.storage)?.owner;
-  if info.sender != owner { 
-    return Err(ContractError::Unauthorized {})
+struct Purchase {
+  tokens: Uint128,
+  fee: Coin,
+  net: Uint128,
+  refund: Coin,
+}
+
+// Splits a payment into the fee, taken from the amount paid and rounded up, and the tokens the rest
+// buys once `supply` are sold. Tokens are rounded down and their cost is rounded up, so nothing is
+// ever sold below the curve. Whatever is left over after paying for whole tokens is refunded.
+fn quote_purchase(deps: Deps, state: &State, curve: &Curve, supply: Uint128, paid: &Coin) -> Result<Purchase, ContractError> {
+  let curve = curve.scale(exchange_rate(deps, state, &paid.denom)?)?;
+  if curve.spot_price(supply)?.is_zero() {
+    return Err(ContractError::InvalidPrice {});
   }
+  let fee = mul_ceil(paid.amount, Decimal::from_ratio(state.fee_bps, MAX_FEE_BPS));
+  let net = paid.amount - fee;
+  let tokens = curve.tokens_for(supply, net)?;
+  let refund = net - curve.cost(supply, tokens)?;
 
-  let luna_balance = deps.querier.query_balance(env.contract.address, String::from("uluna"))?;
-  if luna_balance.amount.u128() < amount as u128 {
-    return Err(ContractError::InvalidQuantity{});
+  Ok(Purchase {
+    tokens,
+    fee: coin(fee.u128(), &paid.denom),
+    net,
+    refund: coin(refund.u128(), &paid.denom),
+  })
+}
+
+// Units of `denom` per uluna, which has to be uluna or one of the accepted denoms.
+fn exchange_rate(deps: Deps, state: &State, denom: &str) -> Result<Decimal, ContractError> {
+  if denom == "uluna" {
+    return Ok(Decimal::one());
+  }
+  if !state.accepted_denoms.iter().any(|accepted| accepted == denom) {
+    return Err(ContractError::UnsupportedDenom { denom: denom.to_string() });
   }
 
-  let msg = BankMsg::Send {
-    to_address: owner.to_string(),
-    amount: vec![coin(amount as u128, String::from("uluna"))],
+  Ok(query_exchange_rate(&deps.querier, "uluna", denom)?)
+}
+
+// The curve a buy is priced on, in uluna. On a bonding curve every buy moves the supply that sells
+// are paid from, so a cheaper tier price could be sold back at a profit and tier prices only
+// replace the oracle price.
+fn purchase_curve(deps: Deps, env: &Env, state: &State, tier: Option<&Tier>) -> Result<Curve, ContractError> {
+  match (&state.curve, tier.and_then(|tier| tier.price)) {
+    (None, Some(price)) => Ok(Curve::Constant { price }),
+    _ => buy_curve(deps, env, state),
+  }
+}
+
+// The curve buys are priced on, in uluna: the bonding curve, or the fresh oracle price for every token.
+fn buy_curve(deps: Deps, env: &Env, state: &State) -> Result<Curve, ContractError> {
+  match &state.curve {
+    Some(curve) => Ok(curve.clone()),
+    None => Ok(Curve::Constant { price: get_fresh_price(deps, env)?.price }),
+  }
+}
+
+pub fn try_receive(deps: DepsMut, env: Env, info: MessageInfo, cw20_msg: Cw20ReceiveMsg) -> Result<Response, ContractError> {
+  // only Lemons can be sold here
+  let token_address = STATE.load(deps.storage)?.token_address;
+  if info.sender != token_address {
+    return Err(ContractError::Unauthorized {});
+  }
+
+  let seller = deps.api.addr_validate(&cw20_msg.sender)?;
+  match from_binary(&cw20_msg.msg)? {
+    Cw20HookMsg::Sell {} => try_sell(deps, env, seller, cw20_msg.amount),
+  }
+}
+
+pub fn try_sell(deps: DepsMut, env: Env, seller: Addr, tokens_received: Uint128) -> Result<Response, ContractError> {
+  let state = STATE.load(deps.storage)?;
+  let sale = quote_sale(deps.as_ref(), &env, &state, tokens_received)?;
+  let luna_to_be_sent = sale.luna_value.saturating_sub(sale.spread_amount);
+  if luna_to_be_sent.is_zero() {
+    return Err(ContractError::InvalidQuantity {});
+  }
+
+  let luna_in_contract = treasury_balance(deps.as_ref(), &env, &AssetInfo::native("uluna"))?;
+  if luna_in_contract < luna_to_be_sent {
+    return Err(ContractError::InsufficientLuna { required: luna_to_be_sent, available: luna_in_contract });
+  }
+
+  if let Some(supply) = sale.supply {
+    CURVE_SUPPLY.save(deps.storage, &supply)?;
+  }
+
+  Ok(Response::new()
+    .add_attributes(vec![
+      ("method", "try_sell"),
+      ("seller", seller.as_str()),
+      ("price", &sale.price.to_string()),
+      ("coins_received", &tokens_received.to_string()),
+      ("spread_amount", &sale.spread_amount.to_string()),
+      ("luna_sent", &luna_to_be_sent.to_string()),
+    ])
+    .add_message(CosmosMsg::Bank(BankMsg::Send {
+      to_address: seller.to_string(),
+      amount: vec![coin(luna_to_be_sent.u128(), "uluna")],
+    })))
+}
+
+struct Sale {
+  // the price after the sale
+  price: Decimal,
+  luna_value: Uint128,
+  spread_amount: Uint128,
+  // the curve supply after the sale, None without a curve
+  supply: Option<Uint128>,
+}
+
+// Values tokens sold back at the oracle price, or down the curve from the current supply. The
+// value and the spread are both rounded in the contract's favour.
+fn quote_sale(deps: Deps, env: &Env, state: &State, tokens: Uint128) -> Result<Sale, ContractError> {
+  let (price, luna_value, supply) = match &state.curve {
+    Some(curve) => {
+      let supply = CURVE_SUPPLY.load(deps.storage)?;
+      if tokens > supply {
+        return Err(ContractError::CurveSupplyExceeded { amount: tokens, supply });
+      }
+      let luna_value = curve.proceeds(supply, tokens)?;
+      (curve.spot_price(supply - tokens)?, luna_value, Some(supply - tokens))
+    }
+    None => {
+      let price_in_luna = get_fresh_price(deps, env)?.price;
+      if price_in_luna.is_zero() {
+        return Err(ContractError::InvalidPrice {});
+      }
+      (price_in_luna, tokens * price_in_luna, None)
+    }
   };
 
+  Ok(Sale { price, luna_value, spread_amount: mul_ceil(luna_value, state.spread), supply })
+}
+
+pub fn try_update_spread(deps: DepsMut, info: MessageInfo, spread: Decimal) -> Result<Response, ContractError> {
+  let mut state = STATE.load(deps.storage)?;
+  if info.sender != state.owner {
+    return Err(ContractError::Unauthorized {});
+  }
+  if spread >= Decimal::one() {
+    return Err(ContractError::InvalidSpread {});
+  }
+
+  state.spread = spread;
+  STATE.save(deps.storage, &state)?;
+
+  Ok(Response::new().add_attributes(vec![
+    ("method", "try_update_spread"),
+    ("spread", &spread.to_string()),
+  ]))
+}
+
+pub fn try_update_fee_config(deps: DepsMut, info: MessageInfo, fee_bps: u16, fee_recipient: Addr) -> Result<Response, ContractError> {
+  let mut state = STATE.load(deps.storage)?;
+  if info.sender != state.owner {
+    return Err(ContractError::Unauthorized {});
+  }
+  if fee_bps >= MAX_FEE_BPS {
+    return Err(ContractError::InvalidFee {});
+  }
+
+  state.fee_bps = fee_bps;
+  state.fee_recipient = deps.api.addr_validate(fee_recipient.as_str())?;
+  STATE.save(deps.storage, &state)?;
+
+  Ok(Response::new().add_attributes(vec![
+    ("method", "try_update_fee_config"),
+    ("fee_bps", &fee_bps.to_string()),
+    ("fee_recipient", state.fee_recipient.as_str()),
+  ]))
+}
+
+pub fn try_withdraw_fees(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+  let state = STATE.load(deps.storage)?;
+  if info.sender != state.owner && info.sender != state.fee_recipient {
+    return Err(ContractError::Unauthorized {});
+  }
+
+  let fees = accumulated_fees(deps.as_ref())?;
+  if fees.is_empty() {
+    return Err(ContractError::InvalidQuantity {});
+  }
+  for fee in &fees {
+    ACCUMULATED_FEES.remove(deps.storage, &fee.denom);
+  }
+
+  let mut response = Response::new().add_attributes(vec![
+    ("method", "try_withdraw_fees"),
+    ("fee_recipient", state.fee_recipient.as_str()),
+  ]);
+  for fee in &fees {
+    response = response.add_attribute("amount", fee.to_string());
+  }
+
+  Ok(response.add_message(CosmosMsg::Bank(BankMsg::Send {
+    to_address: state.fee_recipient.to_string(),
+    amount: fees,
+  })))
+}
+
+pub fn try_update_accepted_denoms(deps: DepsMut, info: MessageInfo, denoms: Vec<String>) -> Result<Response, ContractError> {
+  let mut state = STATE.load(deps.storage)?;
+  if info.sender != state.owner {
+    return Err(ContractError::Unauthorized {});
+  }
+
+  state.accepted_denoms = denoms;
+  STATE.save(deps.storage, &state)?;
+
+  Ok(Response::new().add_attributes(vec![
+    ("method", "try_update_accepted_denoms"),
+    ("denoms", &state.accepted_denoms.join(",")),
+  ]))
+}
+
+pub fn try_update_sale_limits(deps: DepsMut, info: MessageInfo, sale_limits: SaleLimits) -> Result<Response, ContractError> {
+  let mut state = STATE.load(deps.storage)?;
+  if info.sender != state.owner {
+    return Err(ContractError::Unauthorized {});
+  }
+  validate_sale_limits(&sale_limits)?;
+
+  state.sale_limits = sale_limits;
+  STATE.save(deps.storage, &state)?;
+
+  Ok(Response::new().add_attribute("method", "try_update_sale_limits"))
+}
+
+pub fn try_claim(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+  let now = env.block.time.seconds();
+  let records = VESTING_RECORDS
+    .prefix(&info.sender)
+    .range(deps.storage, None, None, Order::Ascending)
+    .collect::<StdResult<Vec<_>>>()?;
+
+  let mut claimed = Uint128::zero();
+  for (id, mut record) in records {
+    let claimable = vested_amount(&record, now) - record.claimed;
+    if claimable.is_zero() {
+      continue;
+    }
+    record.claimed += claimable;
+    claimed += claimable;
+    VESTING_RECORDS.save(deps.storage, (&info.sender, U64Key::from(id)), &record)?;
+  }
+  if claimed.is_zero() {
+    return Err(ContractError::NothingToClaim {});
+  }
+  TOKENS_LOCKED.update(deps.storage, |locked| -> StdResult<_> { Ok(locked.checked_sub(claimed)?) })?;
+
+  let token_address = STATE.load(deps.storage)?.token_address;
   Ok(Response::new()
     .add_attributes(vec![
-      ("initial_luna_balance", luna_balance.to_string()),
-      ("amount", amount.to_string())
+      ("method", "try_claim"),
+      ("claimed", &claimed.to_string()),
     ])
-    .add_message(CosmosMsg::Bank(msg)))
+    .add_message(transfer_tokens_msg(&token_address, &info.sender, claimed)?))
 }
 
-fn get_price(deps: Deps) -> Result<PriceResponse, ContractError> {
-  let oracle_address = STATE.load(deps.storage)?.oracle_address;
+pub fn try_update_vesting(deps: DepsMut, info: MessageInfo, vesting: Option<Vesting>) -> Result<Response, ContractError> {
+  let mut state = STATE.load(deps.storage)?;
+  if info.sender != state.owner {
+    return Err(ContractError::Unauthorized {});
+  }
+  validate_vesting(&vesting)?;
+
+  state.vesting = vesting;
+  STATE.save(deps.storage, &state)?;
+
+  Ok(Response::new().add_attribute("method", "try_update_vesting"))
+}
+
+fn validate_vesting(vesting: &Option<Vesting>) -> Result<(), ContractError> {
+  match vesting {
+    Some(vesting) if vesting.cliff > vesting.duration => Err(ContractError::InvalidVesting {}),
+    _ => Ok(()),
+  }
+}
+
+// Nothing vests before the cliff. After it the tokens vest linearly from the purchase to the end
+// of the schedule, so the cliff releases everything vested up to then at once.
+fn vested_amount(record: &VestingRecord, now: u64) -> Uint128 {
+  if now < record.cliff_time {
+    return Uint128::zero();
+  }
+  if now >= record.end_time {
+    return record.amount;
+  }
+  record.amount.multiply_ratio(now - record.start_time, record.end_time - record.start_time)
+}
+
+fn transfer_tokens_msg(token_address: &Addr, recipient: &Addr, amount: Uint128) -> StdResult<CosmosMsg> {
+  Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+    contract_addr: token_address.to_string(),
+    msg: to_binary(&Cw20ExecuteMsg::Transfer { recipient: recipient.to_string(), amount })?,
+    funds: vec![],
+  }))
+}
+
+// Lemons held by the contract, less the ones bought and waiting to be claimed.
+fn tokens_for_sale(deps: Deps, env: &Env, token_address: &Addr) -> StdResult<Uint128> {
+  let balance = query_token_balance(&deps.querier, token_address, &env.contract.address)?;
+  Ok(balance.saturating_sub(TOKENS_LOCKED.load(deps.storage)?))
+}
+
+pub fn try_update_presale(deps: DepsMut, info: MessageInfo, public_sale_time: Option<u64>) -> Result<Response, ContractError> {
+  let mut state = STATE.load(deps.storage)?;
+  if info.sender != state.owner {
+    return Err(ContractError::Unauthorized {});
+  }
+
+  state.public_sale_time = public_sale_time;
+  STATE.save(deps.storage, &state)?;
+
+  Ok(Response::new().add_attributes(vec![
+    ("method", "try_update_presale"),
+    ("public_sale_time", &public_sale_time.map_or_else(|| String::from("none"), |time| time.to_string())),
+  ]))
+}
+
+pub fn try_update_allow_list(
+  deps: DepsMut,
+  info: MessageInfo,
+  add: Vec<AllowListEntry>,
+  remove: Vec<String>,
+) -> Result<Response, ContractError> {
+  let owner = STATE.load(deps.storage)?.owner;
+  if info.sender != owner {
+    return Err(ContractError::Unauthorized {});
+  }
+
+  for entry in &add {
+    let address = deps.api.addr_validate(&entry.address)?;
+    ALLOW_LIST.save(deps.storage, &address, &entry.tier)?;
+  }
+  for address in &remove {
+    let address = deps.api.addr_validate(address)?;
+    ALLOW_LIST.remove(deps.storage, &address);
+  }
+
+  Ok(Response::new().add_attributes(vec![
+    ("method", "try_update_allow_list"),
+    ("added", &add.len().to_string()),
+    ("removed", &remove.len().to_string()),
+  ]))
+}
+
+// The buyer's tier while the presale runs, which only allow-listed buyers can buy in. Once the
+// public sale starts everyone buys on the same terms and this returns None.
+fn presale_tier(deps: Deps, state: &State, env: &Env, buyer: &Addr) -> Result<Option<Tier>, ContractError> {
+  let public_sale_time = match state.public_sale_time {
+    Some(public_sale_time) if env.block.time.seconds() < public_sale_time => public_sale_time,
+    _ => return Ok(None),
+  };
+
+  match ALLOW_LIST.may_load(deps.storage, buyer)? {
+    Some(tier) => Ok(Some(tier)),
+    None => Err(ContractError::NotAllowListed { address: buyer.to_string(), public_sale_time }),
+  }
+}
+
+fn validate_sale_limits(sale_limits: &SaleLimits) -> Result<(), ContractError> {
+  if let (Some(start_time), Some(end_time)) = (sale_limits.start_time, sale_limits.end_time) {
+    if end_time <= start_time {
+      return Err(ContractError::InvalidSaleWindow {});
+    }
+  }
+  Ok(())
+}
+
+// Fees not yet withdrawn, one coin per denom.
+fn accumulated_fees(deps: Deps) -> StdResult<Vec<Coin>> {
+  ACCUMULATED_FEES
+    .range(deps.storage, None, None, Order::Ascending)
+    .map(|item| {
+      let (denom, amount) = item?;
+      Ok(Coin { denom: String::from_utf8(denom)?, amount })
+    })
+    .collect()
+}
+
+pub fn try_withdraw(
+  deps: DepsMut,
+  env: Env,
+  info: MessageInfo,
+  assets: Vec<AssetUnchecked>,
+  recipient: Option<String>,
+) -> Result<Response, ContractError> {
+  let recipient = withdrawal_recipient(deps.as_ref(), &info, recipient)?;
+
+  // the same asset listed twice is paid out once, for the total
+  let mut payouts: Vec<Asset> = vec![];
+  for asset in assets {
+    let asset = asset.check(deps.api)?;
+    match payouts.iter_mut().find(|payout| payout.info == asset.info) {
+      Some(payout) => payout.amount = payout.amount.checked_add(asset.amount).map_err(StdError::from)?,
+      None => payouts.push(asset),
+    }
+  }
+
+  for payout in &payouts {
+    if payout.amount.is_zero() {
+      return Err(ContractError::InvalidQuantity {});
+    }
+    let available = treasury_balance(deps.as_ref(), &env, &payout.info)?;
+    if available < payout.amount {
+      return Err(ContractError::InsufficientTreasuryBalance {
+        asset: payout.info.to_string(),
+        requested: payout.amount,
+        available,
+      });
+    }
+  }
+
+  payout_response("try_withdraw", &recipient, payouts)
+}
+
+pub fn try_withdraw_all(deps: DepsMut, env: Env, info: MessageInfo, recipient: Option<String>) -> Result<Response, ContractError> {
+  let recipient = withdrawal_recipient(deps.as_ref(), &info, recipient)?;
+  let token_address = STATE.load(deps.storage)?.token_address;
+
+  let mut assets: Vec<AssetInfo> = deps
+    .querier
+    .query_all_balances(&env.contract.address)?
+    .into_iter()
+    .map(|coin| AssetInfo::Native(coin.denom))
+    .collect();
+  assets.push(AssetInfo::Cw20(token_address));
+
+  let mut payouts = vec![];
+  for info in assets {
+    let amount = treasury_balance(deps.as_ref(), &env, &info)?;
+    if !amount.is_zero() {
+      payouts.push(Asset::new(info, amount));
+    }
+  }
+
+  payout_response("try_withdraw_all", &recipient, payouts)
+}
+
+// Only the owner withdraws from the treasury, to itself unless another recipient is given.
+fn withdrawal_recipient(deps: Deps, info: &MessageInfo, recipient: Option<String>) -> Result<Addr, ContractError> {
+  let owner = STATE.load(deps.storage)?.owner;
+  if info.sender != owner {
+    return Err(ContractError::Unauthorized {});
+  }
+
+  match recipient {
+    Some(recipient) => Ok(deps.api.addr_validate(&recipient)?),
+    None => Ok(owner),
+  }
+}
+
+// Transfers every payout to the recipient, logging each as a `payout` attribute.
+fn payout_response(method: &str, recipient: &Addr, payouts: Vec<Asset>) -> Result<Response, ContractError> {
+  let mut response = Response::new().add_attributes(vec![
+    ("method", method),
+    ("recipient", recipient.as_str()),
+  ]);
+  for payout in payouts {
+    response = response
+      .add_attribute("payout", payout.to_string())
+      .add_message(payout.transfer_msg(recipient)?);
+  }
+
+  Ok(response)
+}
+
+// The contract's balance of an asset, leaving out the fees that belong to the fee recipient and
+// the Lemons that belong to buyers.
+fn treasury_balance(deps: Deps, env: &Env, info: &AssetInfo) -> Result<Uint128, ContractError> {
+  match info {
+    AssetInfo::Native(denom) => {
+      let balance = info.query_balance(&deps.querier, &env.contract.address)?;
+      let fees = ACCUMULATED_FEES.may_load(deps.storage, denom)?.unwrap_or_default();
+      Ok(balance.saturating_sub(fees))
+    }
+    AssetInfo::Cw20(token) if *token == STATE.load(deps.storage)?.token_address => {
+      Ok(tokens_for_sale(deps, env, token)?)
+    }
+    AssetInfo::Cw20(_) => Ok(info.query_balance(&deps.querier, &env.contract.address)?),
+  }
+}
+
+fn get_price(deps: Deps) -> StdResult<PriceResponse> {
+  let state = STATE.load(deps.storage)?;
+  let oracle_address = state
+    .oracle_address
+    .ok_or_else(|| StdError::generic_err("No oracle is configured"))?;
+  // the oracle quotes Lemons in uluna
+  let pair = Pair::new(AssetInfo::cw20(state.token_address), AssetInfo::native("uluna"));
   let price_response: PriceResponse = deps.querier.query_wasm_smart(
       oracle_address,
-      &QueryMsg::QueryPrice {},
+      &OracleQueryMsg::QueryPrice { pair },
   )?;
   Ok(price_response)
 }
 
-fn get_balance_of_cw20(deps: Deps, address: Addr) -> Result<BalanceResponse, ContractError> {
-  let token_address = STATE.load(deps.storage)?.token_address;
-  let balance_response: BalanceResponse = deps.querier.query_wasm_smart(
-      token_address,
-      &QueryMsg::Balance { address: address }
-  )?;
-  Ok(balance_response)
+// Loads the oracle price and rejects it when it is older than the configured max age.
+fn get_fresh_price(deps: Deps, env: &Env) -> Result<PriceResponse, ContractError> {
+  let price_response = get_price(deps)?;
+  if price_response.status == PriceStatus::Paused {
+    return Err(ContractError::PriceFeedPaused {});
+  }
+  if let Some(max_age) = STATE.load(deps.storage)?.max_price_age {
+    let age = env.block.time.seconds().saturating_sub(price_response.last_updated_time);
+    if age > max_age {
+      return Err(ContractError::StalePrice { age, max_age });
+    }
+  }
+  Ok(price_response)
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
@@ -142,33 +825,195 @@ pub fn migrate(_deps: DepsMut, _env: Env, _msg: Empty) -> StdResult<Response> {
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
-      QueryMsg::QueryPrice {} => { 
-        let price_response: PriceResponse = get_price(deps).unwrap();
-        return to_binary(&price_response)
-      },
-      QueryMsg::Balance { address } => {
-        return to_binary(&{ address })
-      }
+      QueryMsg::QueryPrice {} => to_binary(&query_price(deps, env)?),
+      QueryMsg::Config {} => to_binary(&query_config(deps)?),
+      QueryMsg::Simulate { offer_amount, buyer } => to_binary(&query_simulate(deps, env, offer_amount, buyer)?),
+      QueryMsg::SimulateSell { amount } => to_binary(&query_simulate_sell(deps, env, amount)?),
+      QueryMsg::Curve {} => to_binary(&query_curve(deps)?),
+      QueryMsg::Inventory {} => to_binary(&query_inventory(deps, env)?),
+      QueryMsg::Treasury {} => to_binary(&query_treasury(deps, env)?),
+      QueryMsg::Fees {} => to_binary(&query_fees(deps)?),
+      QueryMsg::AcceptedDenoms {} => to_binary(&query_accepted_denoms(deps)?),
+      QueryMsg::Allowance { address } => to_binary(&query_allowance(deps, env, address)?),
+      QueryMsg::AllowList { address } => to_binary(&query_allow_list(deps, address)?),
+      QueryMsg::VestingStatus { address } => to_binary(&query_vesting_status(deps, env, address)?),
+    }
+}
+
+fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
+  let state = STATE.load(deps.storage)?;
+  Ok(ConfigResponse {
+    owner: state.owner,
+    token_address: state.token_address,
+    oracle_address: state.oracle_address,
+    max_price_age: state.max_price_age,
+    spread: state.spread,
+    fee_bps: state.fee_bps,
+    fee_recipient: state.fee_recipient,
+    sale_limits: state.sale_limits,
+    public_sale_time: state.public_sale_time,
+    curve: state.curve,
+    vesting: state.vesting,
+  })
+}
+
+fn query_vesting_status(deps: Deps, env: Env, address: String) -> StdResult<VestingStatusResponse> {
+  let address = deps.api.addr_validate(&address)?;
+  let now = env.block.time.seconds();
+
+  let mut status = VestingStatusResponse { vested: Uint128::zero(), claimed: Uint128::zero(), locked: Uint128::zero() };
+  for item in VESTING_RECORDS.prefix(&address).range(deps.storage, None, None, Order::Ascending) {
+    let (_, record) = item?;
+    let vested = vested_amount(&record, now);
+    status.vested += vested;
+    status.claimed += record.claimed;
+    status.locked += record.amount - vested;
+  }
+  Ok(status)
+}
+
+// The bonding curve's price for the next token, as of this block, or the oracle price.
+fn query_price(deps: Deps, env: Env) -> StdResult<PriceResponse> {
+  match STATE.load(deps.storage)?.curve {
+    Some(curve) => Ok(PriceResponse {
+      price: curve.spot_price(CURVE_SUPPLY.load(deps.storage)?)?,
+      last_updated_height: env.block.height,
+      last_updated_time: env.block.time.seconds(),
+      status: PriceStatus::Active,
+    }),
+    None => get_price(deps),
+  }
+}
+
+fn query_curve(deps: Deps) -> StdResult<CurveResponse> {
+  Ok(CurveResponse {
+    curve: STATE.load(deps.storage)?.curve,
+    supply: CURVE_SUPPLY.load(deps.storage)?,
+  })
+}
+
+fn query_allowance(deps: Deps, env: Env, address: String) -> StdResult<AllowanceResponse> {
+  let address = deps.api.addr_validate(&address)?;
+  let state = STATE.load(deps.storage)?;
+  let purchased = PURCHASES.may_load(deps.storage, &address)?.unwrap_or_default();
+  let tokens_sold = TOKENS_SOLD.load(deps.storage)?;
+
+  // buyers left off the presale can't buy anything yet
+  let tier_max_tokens = match presale_tier(deps, &state, &env, &address) {
+    Ok(tier) => tier.and_then(|tier| tier.max_tokens),
+    Err(_) => Some(Uint128::zero()),
+  };
+
+  let remaining = [
+    state.sale_limits.max_tokens_per_buyer.map(|max_tokens| max_tokens.saturating_sub(purchased)),
+    tier_max_tokens.map(|max_tokens| max_tokens.saturating_sub(purchased)),
+    state.sale_limits.max_tokens_sold.map(|max_tokens| max_tokens.saturating_sub(tokens_sold)),
+  ]
+  .iter()
+  .flatten()
+  .min()
+  .copied();
+
+  Ok(AllowanceResponse { purchased, remaining })
+}
+
+fn query_allow_list(deps: Deps, address: String) -> StdResult<AllowListResponse> {
+  let address = deps.api.addr_validate(&address)?;
+  Ok(AllowListResponse {
+    tier: ALLOW_LIST.may_load(deps.storage, &address)?,
+    public_sale_time: STATE.load(deps.storage)?.public_sale_time,
+  })
+}
+
+fn query_simulate(deps: Deps, env: Env, offer_amount: Coin, buyer: Option<String>) -> StdResult<SimulateResponse> {
+  simulate_purchase(deps, &env, &offer_amount, buyer).map_err(|err| StdError::generic_err(err.to_string()))
+}
+
+// Runs the same checks as Buy, so an offer that simulates fine can be bought by `buyer`.
+fn simulate_purchase(deps: Deps, env: &Env, offer_amount: &Coin, buyer: Option<String>) -> Result<SimulateResponse, ContractError> {
+  let state = STATE.load(deps.storage)?;
+  let buyer = buyer.map(|buyer| deps.api.addr_validate(&buyer)).transpose()?;
+
+  let quote = quote_buy(deps, env, &state, buyer.as_ref(), std::slice::from_ref(offer_amount), None)?;
+  let purchase = quote.purchases.into_iter().next().ok_or(ContractError::CoinMismatch {})?;
+  Ok(SimulateResponse { tokens_out: purchase.tokens, fee: purchase.fee, refund: purchase.refund })
+}
+
+fn query_simulate_sell(deps: Deps, env: Env, amount: Uint128) -> StdResult<SimulateSellResponse> {
+  let state = STATE.load(deps.storage)?;
+  let sale = quote_sale(deps, &env, &state, amount).map_err(|err| StdError::generic_err(err.to_string()))?;
+  Ok(SimulateSellResponse {
+    return_amount: sale.luna_value.saturating_sub(sale.spread_amount),
+    spread_amount: sale.spread_amount,
+  })
+}
+
+fn query_inventory(deps: Deps, env: Env) -> StdResult<InventoryResponse> {
+  let token_address = STATE.load(deps.storage)?.token_address;
+  let balance = tokens_for_sale(deps, &env, &token_address)?;
+  Ok(InventoryResponse { token_address, balance })
+}
+
+fn query_treasury(deps: Deps, env: Env) -> StdResult<TreasuryResponse> {
+  let mut balances = vec![];
+  for balance in query_all_balances(&deps.querier, &env.contract.address)? {
+    let fees = ACCUMULATED_FEES.may_load(deps.storage, &balance.denom)?.unwrap_or_default();
+    let amount = balance.amount.saturating_sub(fees);
+    if !amount.is_zero() {
+      balances.push(Coin { denom: balance.denom, amount });
     }
+  }
+  Ok(TreasuryResponse { balances })
+}
+
+fn query_fees(deps: Deps) -> StdResult<FeesResponse> {
+  let state = STATE.load(deps.storage)?;
+  Ok(FeesResponse {
+    fee_bps: state.fee_bps,
+    fee_recipient: state.fee_recipient,
+    accumulated_fees: accumulated_fees(deps)?,
+  })
+}
+
+fn query_accepted_denoms(deps: Deps) -> StdResult<AcceptedDenomsResponse> {
+  let mut denoms = vec![String::from("uluna")];
+  denoms.extend(STATE.load(deps.storage)?.accepted_denoms);
+  Ok(AcceptedDenomsResponse { denoms })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use cosmwasm_std::testing::{mock_env, mock_info, MOCK_CONTRACT_ADDR};
-    use cosmwasm_std::{coins, from_binary, Addr};
+    use cosmwasm_std::coins;
     use testing::mock_querier::{mock_dependencies};
 
     const TOKEN: &str = "hyp0000";
     const ORACLE: &str = "oracle000";
 
+    fn default_instantiate_msg() -> InstantiateMsg {
+      InstantiateMsg {
+        token_address: Addr::unchecked(TOKEN),
+        oracle_address: Some(Addr::unchecked(ORACLE)),
+        max_price_age: None,
+        spread: None,
+        fee_bps: None,
+        fee_recipient: None,
+        accepted_denoms: None,
+        sale_limits: None,
+        public_sale_time: None,
+        curve: None,
+        vesting: None,
+      }
+    }
+
     #[test]
     fn proper_initialization() {
       let mut deps = mock_dependencies(&coins(2, "token"));
 
-      let msg = InstantiateMsg { token_address: Addr::unchecked(TOKEN), oracle_address: Addr::unchecked(ORACLE) };
+      let msg = default_instantiate_msg();
       let info = mock_info("creator", &coins(1000, "earth"));
       let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
     }
@@ -176,41 +1021,717 @@ mod tests {
     #[test]
     fn try_query() {
       let mut deps = mock_dependencies(&coins(2, "token"));
-      deps.querier.with_oracle_price(15);
+      deps.querier.with_oracle_price(Decimal::percent(1500));
 
-      let msg = InstantiateMsg { token_address: Addr::unchecked(TOKEN), oracle_address: Addr::unchecked("oracle000") };
+      let msg = default_instantiate_msg();
       let info = mock_info("creator", &coins(1_000_000, "uluna"));
       let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
 
       // it worked, let's query the state
       let res = query(deps.as_ref(), mock_env(), QueryMsg::QueryPrice {}).unwrap();
       let price_response: PriceResponse = from_binary(&res).unwrap();
-      assert_eq!(15, price_response.price);
+      assert_eq!(Decimal::percent(1500), price_response.price);
+    }
+
+    #[test]
+    fn try_query_config_and_balances() {
+      let mut deps = mock_dependencies(&[coin(1_000, "uluna"), coin(50, "uusd")]);
+      deps.querier.with_oracle_price(Decimal::percent(250));
+      deps.querier.with_exchange_rates(&[("uusd", Decimal::percent(50))]);
+      deps.querier.with_token_balances(&[(
+        &TOKEN.to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::new(1_000))],
+      )]);
+
+      let msg = InstantiateMsg {
+        fee_bps: Some(100),
+        accepted_denoms: Some(vec![String::from("uusd")]),
+        ..default_instantiate_msg()
+      };
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      let res: ConfigResponse = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+      assert_eq!(Addr::unchecked("creator"), res.owner);
+      assert_eq!(Addr::unchecked(TOKEN), res.token_address);
+      assert_eq!(100, res.fee_bps);
+
+      // 1010 uluna pay a fee of 11, and the other 999 buy 399 tokens and 1 uluna is refunded
+      let msg = QueryMsg::Simulate { buyer: None, offer_amount: coin(1_010, "uluna") };
+      let res: SimulateResponse = from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+      assert_eq!(SimulateResponse { tokens_out: Uint128::new(399), fee: coin(11, "uluna"), refund: coin(1, "uluna") }, res);
+
+      let msg = QueryMsg::Simulate { buyer: None, offer_amount: coin(100, "uusd") };
+      let res: SimulateResponse = from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+      assert_eq!(Uint128::new(79), res.tokens_out);
+
+      let msg = QueryMsg::Simulate { buyer: None, offer_amount: coin(100, "ukrw") };
+      assert!(query(deps.as_ref(), mock_env(), msg).is_err());
+
+      let res: InventoryResponse = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Inventory {}).unwrap()).unwrap();
+      assert_eq!(Uint128::new(1_000), res.balance);
+
+      // fees are not part of the treasury
+      ACCUMULATED_FEES.save(deps.as_mut().storage, "uusd", &Uint128::new(50)).unwrap();
+      let res: TreasuryResponse = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Treasury {}).unwrap()).unwrap();
+      assert_eq!(coins(1_000, "uluna"), res.balances);
+
+      // oracle failures come back as errors
+      deps.querier.with_oracle_status(PriceStatus::Paused);
+      let msg = QueryMsg::Simulate { buyer: None, offer_amount: coin(1_010, "uluna") };
+      assert!(query(deps.as_ref(), mock_env(), msg).is_err());
+    }
+
+    #[test]
+    fn try_query_unreachable_oracle() {
+      let mut deps = mock_dependencies(&[]);
+
+      let msg = InstantiateMsg {
+        oracle_address: Some(Addr::unchecked("nowhere")),
+        ..default_instantiate_msg()
+      };
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      assert!(query(deps.as_ref(), mock_env(), QueryMsg::QueryPrice {}).is_err());
     }
 
     #[test]
     fn try_buy() {
       let mut deps = mock_dependencies(&coins(1000, TOKEN));
-      deps.querier.with_oracle_price(10);
+      deps.querier.with_oracle_price(Decimal::percent(1000));
       deps.querier.with_token_balances(&[(
         &TOKEN.to_string(),
         &[(
             &MOCK_CONTRACT_ADDR.to_string(),
-            &Uint128::new(1_000_000_000_000 as u128),
+            &Uint128::new(1_000_000_000_000),
         )],
       )]);
 
-      let msg = InstantiateMsg { token_address: Addr::unchecked(TOKEN), oracle_address: Addr::unchecked("oracle000") };
+      let msg = default_instantiate_msg();
       let info = mock_info("creator", &coins(1_000_000, "uluna"));
       let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-      let msg = ExecuteMsg::Buy {};
+      let msg = ExecuteMsg::Buy { min_tokens_out: None, max_price: None, deadline: None };
       let info = mock_info("buyer", &coins(1_000, String::from("uluna")));
       let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
-      assert_eq!("100", res.attributes[2].value);
+      assert!(res.attributes.contains(&attr("coins_sent", "100")));
     }
 
+    #[test]
+    fn try_buy_refunds_dust() {
+      let mut deps = mock_dependencies(&[]);
+      deps.querier.with_oracle_price(Decimal::percent(250));
+      deps.querier.with_token_balances(&[(
+        &TOKEN.to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::new(1_000_000))],
+      )]);
+
+      let msg = default_instantiate_msg();
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      // 1001 uluna at 2.5 uluna per token buys 400 tokens for 1000 uluna
+      let info = mock_info("buyer", &coins(1_001, "uluna"));
+      let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Buy { min_tokens_out: None, max_price: None, deadline: None }).unwrap();
+      assert!(res.attributes.contains(&attr("coins_sent", "400")));
+      assert!(res.attributes.contains(&attr("refunded", "1uluna")));
+      assert_eq!(
+        CosmosMsg::Bank(BankMsg::Send { to_address: String::from("buyer"), amount: coins(1, "uluna") }),
+        res.messages[1].msg
+      );
+
+      // prices below one uluna per token work too
+      deps.querier.with_oracle_price(Decimal::percent(30));
+      let info = mock_info("buyer", &coins(1_000, "uluna"));
+      let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Buy { min_tokens_out: None, max_price: None, deadline: None }).unwrap();
+      assert!(res.attributes.contains(&attr("coins_sent", "3333")));
+      assert!(res.attributes.contains(&attr("refunded", "0uluna")));
+      assert_eq!(1, res.messages.len());
+    }
+
+    #[test]
+    fn try_buy_enforces_slippage_limits() {
+      let mut deps = mock_dependencies(&[]);
+      deps.querier.with_oracle_price(Decimal::percent(1000));
+      deps.querier.with_token_balances(&[(
+        &TOKEN.to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::new(1_000_000))],
+      )]);
+
+      let msg = default_instantiate_msg();
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      let now = mock_env().block.time.seconds();
+      let msg = ExecuteMsg::Buy { min_tokens_out: None, max_price: None, deadline: Some(now - 1) };
+      let err = execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(1_000, "uluna")), msg).unwrap_err();
+      assert!(matches!(err, ContractError::DeadlineExceeded { .. }));
+
+      let msg = ExecuteMsg::Buy { min_tokens_out: None, max_price: Some(Decimal::percent(900)), deadline: None };
+      let err = execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(1_000, "uluna")), msg).unwrap_err();
+      assert!(matches!(err, ContractError::MaxPriceExceeded { .. }));
+
+      let msg = ExecuteMsg::Buy { min_tokens_out: Some(Uint128::new(101)), max_price: None, deadline: None };
+      let err = execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(1_000, "uluna")), msg).unwrap_err();
+      assert!(matches!(err, ContractError::MinTokensOutNotMet { .. }));
+
+      // limits that are met let the purchase through
+      let msg = ExecuteMsg::Buy {
+        min_tokens_out: Some(Uint128::new(100)),
+        max_price: Some(Decimal::percent(1000)),
+        deadline: Some(now),
+      };
+      let _res = execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(1_000, "uluna")), msg).unwrap();
+    }
+
+    #[test]
+    fn try_buy_rejects_stale_price() {
+      let mut deps = mock_dependencies(&[]);
+      let env = mock_env();
+      deps.querier.with_oracle_price_updated_at(Decimal::percent(1000), env.block.height - 100, env.block.time.seconds() - 600);
+      deps.querier.with_token_balances(&[(
+        &TOKEN.to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::new(1_000_000))],
+      )]);
+
+      let msg = InstantiateMsg {
+        max_price_age: Some(300),
+        ..default_instantiate_msg()
+      };
+      let _res = instantiate(deps.as_mut(), env.clone(), mock_info("creator", &[]), msg).unwrap();
+
+      let info = mock_info("buyer", &coins(1_000, "uluna"));
+      let err = execute(deps.as_mut(), env, info, ExecuteMsg::Buy { min_tokens_out: None, max_price: None, deadline: None }).unwrap_err();
+      assert!(matches!(err, ContractError::StalePrice { age: 600, max_age: 300 }));
+    }
+
+    #[test]
+    fn try_buy_rejects_paused_price() {
+      let mut deps = mock_dependencies(&[]);
+      deps.querier.with_oracle_price(Decimal::percent(1000));
+      deps.querier.with_oracle_status(PriceStatus::Paused);
+
+      let msg = default_instantiate_msg();
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      let info = mock_info("buyer", &coins(1_000, "uluna"));
+      let err = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Buy { min_tokens_out: None, max_price: None, deadline: None }).unwrap_err();
+      assert!(matches!(err, ContractError::PriceFeedPaused {}));
+    }
+
+    #[test]
     fn try_withdraw() {
+      let mut deps = mock_dependencies(&coins(1_000, "uluna"));
+
+      let msg = default_instantiate_msg();
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      let msg = ExecuteMsg::Withdraw { assets: vec![AssetUnchecked::native("uluna", 400u128)], recipient: None };
+      let err = execute(deps.as_mut(), mock_env(), mock_info("buyer", &[]), msg.clone()).unwrap_err();
+      assert!(matches!(err, ContractError::Unauthorized {}));
+
+      let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+      assert_eq!(1, res.messages.len());
+      assert_eq!(
+        CosmosMsg::Bank(BankMsg::Send { to_address: String::from("creator"), amount: coins(400, "uluna") }),
+        res.messages[0].msg
+      );
+
+      // the payouts add up per asset, and each has to be covered by the treasury
+      let msg = ExecuteMsg::Withdraw {
+        assets: vec![AssetUnchecked::native("uluna", 600u128), AssetUnchecked::native("uluna", 600u128)],
+        recipient: None,
+      };
+      let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap_err();
+      assert!(matches!(err, ContractError::InsufficientTreasuryBalance { .. }));
+    }
+
+    #[test]
+    fn try_withdraw_tokens_and_all() {
+      let mut deps = mock_dependencies(&[coin(1_000, "uluna"), coin(50, "uusd")]);
+      deps.querier.with_token_balances(&[
+        (&TOKEN.to_string(), &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::new(300))]),
+        (&String::from("other0000"), &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::new(20))]),
+      ]);
+
+      let msg = default_instantiate_msg();
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      let msg = ExecuteMsg::Withdraw {
+        assets: vec![AssetUnchecked::cw20("other0000", 20u128), AssetUnchecked::native("uusd", 10u128)],
+        recipient: Some(String::from("treasury")),
+      };
+      let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+      assert!(res.attributes.contains(&attr("payout", "cw20:other0000:20")));
+      assert!(res.attributes.contains(&attr("payout", "native:uusd:10")));
+      assert_eq!(
+        CosmosMsg::Wasm(WasmMsg::Execute {
+          contract_addr: String::from("other0000"),
+          msg: to_binary(&Cw20ExecuteMsg::Transfer { recipient: String::from("treasury"), amount: Uint128::new(20) }).unwrap(),
+          funds: vec![],
+        }),
+        res.messages[0].msg
+      );
+
+      let msg = ExecuteMsg::WithdrawAll { recipient: None };
+      let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+      assert_eq!(3, res.messages.len());
+      assert!(res.attributes.contains(&attr("payout", "native:uluna:1000")));
+      assert!(res.attributes.contains(&attr("payout", "native:uusd:50")));
+      assert!(res.attributes.contains(&attr("payout", format!("cw20:{}:300", TOKEN))));
+    }
+
+    #[test]
+    fn try_sell() {
+      let mut deps = mock_dependencies(&coins(1_000, "uluna"));
+      deps.querier.with_oracle_price(Decimal::percent(250));
+
+      let msg = InstantiateMsg {
+        spread: Some(Decimal::percent(1)),
+        ..default_instantiate_msg()
+      };
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      let sell = |amount: u128| {
+        ExecuteMsg::Receive(Cw20ReceiveMsg {
+          sender: String::from("seller"),
+          amount: Uint128::new(amount),
+          msg: to_binary(&Cw20HookMsg::Sell {}).unwrap(),
+        })
+      };
+
+      // only the Lemon contract can call the hook
+      let err = execute(deps.as_mut(), mock_env(), mock_info("fake_token", &[]), sell(100)).unwrap_err();
+      assert!(matches!(err, ContractError::Unauthorized {}));
+
+      // 100 tokens at 2.5 uluna are worth 250 uluna, of which 3 are kept as spread
+      let res = execute(deps.as_mut(), mock_env(), mock_info(TOKEN, &[]), sell(100)).unwrap();
+      assert!(res.attributes.contains(&attr("spread_amount", "3")));
+      assert_eq!(
+        CosmosMsg::Bank(BankMsg::Send { to_address: String::from("seller"), amount: coins(247, "uluna") }),
+        res.messages[0].msg
+      );
+
+      let err = execute(deps.as_mut(), mock_env(), mock_info(TOKEN, &[]), sell(1_000)).unwrap_err();
+      assert!(matches!(err, ContractError::InsufficientLuna { .. }));
+
+      let msg = ExecuteMsg::UpdateSpread { spread: Decimal::one() };
+      let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap_err();
+      assert!(matches!(err, ContractError::InvalidSpread {}));
+    }
+
+    #[test]
+    fn try_buy_charges_fee() {
+      let mut deps = mock_dependencies(&coins(1_000, "uluna"));
+      deps.querier.with_oracle_price(Decimal::percent(1000));
+      deps.querier.with_token_balances(&[(
+        &TOKEN.to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::new(1_000_000))],
+      )]);
+
+      let msg = InstantiateMsg {
+        fee_bps: Some(30),
+        fee_recipient: Some(Addr::unchecked("collector")),
+        ..default_instantiate_msg()
+      };
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      // 0.3% of 1000 uluna is kept as fee, the remaining 997 buy 99 tokens and 7 uluna are refunded
+      let msg = ExecuteMsg::Buy { min_tokens_out: None, max_price: None, deadline: None };
+      let res = execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(1_000, "uluna")), msg).unwrap();
+      assert!(res.attributes.contains(&attr("gross", "1000uluna")));
+      assert!(res.attributes.contains(&attr("fee", "3uluna")));
+      assert!(res.attributes.contains(&attr("net", "997uluna")));
+      assert!(res.attributes.contains(&attr("coins_sent", "99")));
+      assert!(res.attributes.contains(&attr("refunded", "7uluna")));
+
+      let res: FeesResponse = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Fees {}).unwrap()).unwrap();
+      assert_eq!(coins(3, "uluna"), res.accumulated_fees);
+
+      // the principal withdraw leaves the fees alone
+      let msg = ExecuteMsg::Withdraw { assets: vec![AssetUnchecked::native("uluna", 998u128)], recipient: None };
+      let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap_err();
+      assert!(matches!(err, ContractError::InsufficientTreasuryBalance { .. }));
+
+      let err = execute(deps.as_mut(), mock_env(), mock_info("buyer", &[]), ExecuteMsg::WithdrawFees {}).unwrap_err();
+      assert!(matches!(err, ContractError::Unauthorized {}));
+      let res = execute(deps.as_mut(), mock_env(), mock_info("collector", &[]), ExecuteMsg::WithdrawFees {}).unwrap();
+      assert_eq!(
+        CosmosMsg::Bank(BankMsg::Send { to_address: String::from("collector"), amount: coins(3, "uluna") }),
+        res.messages[0].msg
+      );
+
+      let msg = ExecuteMsg::UpdateFeeConfig { fee_bps: 10_000, fee_recipient: Addr::unchecked("collector") };
+      let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap_err();
+      assert!(matches!(err, ContractError::InvalidFee {}));
+    }
+
+    #[test]
+    fn try_buy_with_stable_denoms() {
+      // 1 uluna is worth 0.5 uusd, so tokens at 10 uluna cost 5 uusd
+      let mut deps = mock_dependencies(&[coin(1_000, "uluna"), coin(2_000, "uusd")]);
+      deps.querier.with_oracle_price(Decimal::percent(1000));
+      deps.querier.with_exchange_rates(&[("uusd", Decimal::percent(50)), ("ukrw", Decimal::percent(60_000))]);
+      deps.querier.with_token_balances(&[(
+        &TOKEN.to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::new(1_000_000))],
+      )]);
+
+      let msg = InstantiateMsg {
+        fee_bps: Some(100),
+        accepted_denoms: Some(vec![String::from("uusd")]),
+        ..default_instantiate_msg()
+      };
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      // 1% of each coin is kept as fee, then 994 uluna buy 99 tokens and 1982 uusd buy 396
+      let msg = ExecuteMsg::Buy { min_tokens_out: Some(Uint128::new(495)), max_price: None, deadline: None };
+      let info = mock_info("buyer", &[coin(1_005, "uluna"), coin(2_003, "uusd")]);
+      let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+      assert!(res.attributes.contains(&attr("coins_sent", "495")));
+      assert!(res.attributes.contains(&attr("fee", "21uusd")));
+      assert_eq!(
+        CosmosMsg::Bank(BankMsg::Send {
+          to_address: String::from("buyer"),
+          amount: vec![coin(4, "uluna"), coin(2, "uusd")],
+        }),
+        res.messages[1].msg
+      );
+
+      let res: FeesResponse = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Fees {}).unwrap()).unwrap();
+      assert_eq!(vec![coin(11, "uluna"), coin(21, "uusd")], res.accumulated_fees);
+
+      // denoms that aren't whitelisted are rejected by name
+      let msg = ExecuteMsg::Buy { min_tokens_out: None, max_price: None, deadline: None };
+      let info = mock_info("buyer", &[coin(1_000, "uluna"), coin(100_000, "ukrw")]);
+      let err = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap_err();
+      assert!(matches!(err, ContractError::UnsupportedDenom { denom } if denom == "ukrw"));
+
+      let msg_update = ExecuteMsg::UpdateAcceptedDenoms { denoms: vec![String::from("ukrw")] };
+      let err = execute(deps.as_mut(), mock_env(), mock_info("buyer", &[]), msg_update.clone()).unwrap_err();
+      assert!(matches!(err, ContractError::Unauthorized {}));
+      let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg_update).unwrap();
+      let res: AcceptedDenomsResponse = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::AcceptedDenoms {}).unwrap()).unwrap();
+      assert_eq!(vec![String::from("uluna"), String::from("ukrw")], res.denoms);
+
+      let info = mock_info("buyer", &coins(2_000, "uusd"));
+      let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+      assert!(matches!(err, ContractError::UnsupportedDenom { denom } if denom == "uusd"));
+
+      // fees in every denom are paid out together
+      let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), ExecuteMsg::WithdrawFees {}).unwrap();
+      assert_eq!(
+        CosmosMsg::Bank(BankMsg::Send { to_address: String::from("creator"), amount: vec![coin(11, "uluna"), coin(21, "uusd")] }),
+        res.messages[0].msg
+      );
+    }
+
+    #[test]
+    fn try_buy_enforces_sale_limits() {
+      let mut deps = mock_dependencies(&[]);
+      deps.querier.with_oracle_price(Decimal::percent(1000));
+      deps.querier.with_token_balances(&[(
+        &TOKEN.to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::new(1_000_000))],
+      )]);
+
+      let now = mock_env().block.time.seconds();
+      let msg = InstantiateMsg {
+        sale_limits: Some(SaleLimits {
+          max_tokens_per_buyer: Some(Uint128::new(150)),
+          max_tokens_sold: Some(Uint128::new(250)),
+          start_time: Some(now + 100),
+          end_time: Some(now + 1_000),
+        }),
+        ..default_instantiate_msg()
+      };
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      let buy = ExecuteMsg::Buy { min_tokens_out: None, max_price: None, deadline: None };
+      let mut env = mock_env();
+      let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(1_000, "uluna")), buy.clone()).unwrap_err();
+      assert!(matches!(err, ContractError::SaleNotStarted { .. }));
+
+      // 100 tokens each for alice and bob, then alice only has 50 left
+      env.block.time = env.block.time.plus_seconds(100);
+      let _res = execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(1_000, "uluna")), buy.clone()).unwrap();
+      let _res = execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(1_000, "uluna")), buy.clone()).unwrap();
+      let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(1_000, "uluna")), buy.clone()).unwrap_err();
+      assert!(matches!(err, ContractError::BuyerLimitExceeded { remaining, .. } if remaining == Uint128::new(50)));
+
+      let msg = QueryMsg::Allowance { address: String::from("alice") };
+      let res: AllowanceResponse = from_binary(&query(deps.as_ref(), env.clone(), msg).unwrap()).unwrap();
+      assert_eq!(AllowanceResponse { purchased: Uint128::new(100), remaining: Some(Uint128::new(50)) }, res);
+
+      // the sale cap binds before carol's own limit
+      let err = execute(deps.as_mut(), env.clone(), mock_info("carol", &coins(1_500, "uluna")), buy.clone()).unwrap_err();
+      assert!(matches!(err, ContractError::SaleCapExceeded { remaining, .. } if remaining == Uint128::new(50)));
+      let msg = QueryMsg::Allowance { address: String::from("carol") };
+      let res: AllowanceResponse = from_binary(&query(deps.as_ref(), env.clone(), msg).unwrap()).unwrap();
+      assert_eq!(Some(Uint128::new(50)), res.remaining);
+
+      env.block.time = env.block.time.plus_seconds(900);
+      let err = execute(deps.as_mut(), env, mock_info("carol", &coins(500, "uluna")), buy).unwrap_err();
+      assert!(matches!(err, ContractError::SaleEnded { .. }));
+
+      let msg = ExecuteMsg::UpdateSaleLimits { sale_limits: SaleLimits { start_time: Some(now), end_time: Some(now), ..SaleLimits::default() } };
+      let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap_err();
+      assert!(matches!(err, ContractError::InvalidSaleWindow {}));
+
+      // lifting the limits leaves no cap
+      let msg = ExecuteMsg::UpdateSaleLimits { sale_limits: SaleLimits::default() };
+      let err = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), msg.clone()).unwrap_err();
+      assert!(matches!(err, ContractError::Unauthorized {}));
+      let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+      let msg = QueryMsg::Allowance { address: String::from("alice") };
+      let res: AllowanceResponse = from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+      assert_eq!(None, res.remaining);
+    }
+
+    #[test]
+    fn try_buy_in_presale() {
+      let mut deps = mock_dependencies(&[]);
+      deps.querier.with_oracle_price(Decimal::percent(1000));
+      deps.querier.with_token_balances(&[(
+        &TOKEN.to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::new(1_000_000))],
+      )]);
+
+      let public_sale_time = mock_env().block.time.seconds() + 100;
+      let msg = InstantiateMsg {
+        public_sale_time: Some(public_sale_time),
+        ..default_instantiate_msg()
+      };
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      let tier = Tier { price: Some(Decimal::percent(500)), max_tokens: Some(Uint128::new(300)) };
+      let msg = ExecuteMsg::UpdateAllowList {
+        add: vec![AllowListEntry { address: String::from("alice"), tier: tier.clone() }],
+        remove: vec![],
+      };
+      let err = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), msg.clone()).unwrap_err();
+      assert!(matches!(err, ContractError::Unauthorized {}));
+      let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      let msg = QueryMsg::AllowList { address: String::from("alice") };
+      let res: AllowListResponse = from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+      assert_eq!(AllowListResponse { tier: Some(tier), public_sale_time: Some(public_sale_time) }, res);
+
+      // simulating for a buyer applies their tier's price and the allow-list
+      let msg = QueryMsg::Simulate { buyer: Some(String::from("alice")), offer_amount: coin(1_000, "uluna") };
+      let res: SimulateResponse = from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+      assert_eq!(Uint128::new(200), res.tokens_out);
+      let msg = QueryMsg::Simulate { buyer: Some(String::from("bob")), offer_amount: coin(1_000, "uluna") };
+      assert!(query(deps.as_ref(), mock_env(), msg).is_err());
+
+      // alice buys at her tier's price of 5 uluna, up to her tier's cap
+      let buy = ExecuteMsg::Buy { min_tokens_out: None, max_price: None, deadline: None };
+      let res = execute(deps.as_mut(), mock_env(), mock_info("alice", &coins(1_000, "uluna")), buy.clone()).unwrap();
+      assert!(res.attributes.contains(&attr("coins_sent", "200")));
+      let err = execute(deps.as_mut(), mock_env(), mock_info("alice", &coins(1_000, "uluna")), buy.clone()).unwrap_err();
+      assert!(matches!(err, ContractError::TierLimitExceeded { remaining, .. } if remaining == Uint128::new(100)));
+      let msg = QueryMsg::Simulate { buyer: Some(String::from("alice")), offer_amount: coin(1_000, "uluna") };
+      assert!(query(deps.as_ref(), mock_env(), msg).is_err());
+
+      let err = execute(deps.as_mut(), mock_env(), mock_info("bob", &coins(1_000, "uluna")), buy.clone()).unwrap_err();
+      assert!(matches!(err, ContractError::NotAllowListed { address, .. } if address == "bob"));
+      let msg = QueryMsg::Allowance { address: String::from("bob") };
+      let res: AllowanceResponse = from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+      assert_eq!(Some(Uint128::zero()), res.remaining);
+
+      // the public sale is open to everyone at the oracle price, without the tier's cap
+      let mut env = mock_env();
+      env.block.time = env.block.time.plus_seconds(100);
+      let res = execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(1_000, "uluna")), buy.clone()).unwrap();
+      assert!(res.attributes.contains(&attr("coins_sent", "100")));
+      let _res = execute(deps.as_mut(), env, mock_info("alice", &coins(2_000, "uluna")), buy).unwrap();
+
+      let msg = ExecuteMsg::UpdateAllowList { add: vec![], remove: vec![String::from("alice")] };
+      let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+      let msg = QueryMsg::AllowList { address: String::from("alice") };
+      let res: AllowListResponse = from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+      assert_eq!(None, res.tier);
+    }
+
+    #[test]
+    fn try_buy_and_sell_on_curve() {
+      let mut deps = mock_dependencies(&coins(1_000, "uluna"));
+      deps.querier.with_token_balances(&[(
+        &TOKEN.to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::new(1_000_000))],
+      )]);
+
+      let mut msg = InstantiateMsg {
+        oracle_address: None,
+        ..default_instantiate_msg()
+      };
+      let err = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg.clone()).unwrap_err();
+      assert!(matches!(err, ContractError::NoPriceSource {}));
+      msg.curve = Some(Curve::Linear { base_price: Decimal::zero(), slope: Decimal::percent(1) });
+      let err = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg.clone()).unwrap_err();
+      assert!(matches!(err, ContractError::InvalidCurve {}));
+
+      // the price starts at 1 uluna and rises by 0.01 uluna with every token
+      msg.curve = Some(Curve::Linear { base_price: Decimal::one(), slope: Decimal::percent(1) });
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      // 358 tokens cost 358 + 0.01 * 358^2 / 2 = 998.82 uluna, rounded up
+      let msg = QueryMsg::Simulate { buyer: None, offer_amount: coin(1_000, "uluna") };
+      let res: SimulateResponse = from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+      assert_eq!(SimulateResponse { tokens_out: Uint128::new(358), fee: coin(0, "uluna"), refund: coin(1, "uluna") }, res);
+
+      let msg = ExecuteMsg::Buy { min_tokens_out: None, max_price: None, deadline: None };
+      let res = execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(1_000, "uluna")), msg).unwrap();
+      assert!(res.attributes.contains(&attr("coins_sent", "358")));
+      let res: CurveResponse = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Curve {}).unwrap()).unwrap();
+      assert_eq!(Uint128::new(358), res.supply);
+      let res: PriceResponse = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::QueryPrice {}).unwrap()).unwrap();
+      assert_eq!(Decimal::percent(458), res.price);
+
+      // selling 100 back pays the area between 258 and 358 tokens
+      let msg = QueryMsg::SimulateSell { amount: Uint128::new(100) };
+      let res: SimulateSellResponse = from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+      assert_eq!(Uint128::new(408), res.return_amount);
+
+      let sell = |amount: u128| {
+        ExecuteMsg::Receive(Cw20ReceiveMsg {
+          sender: String::from("buyer"),
+          amount: Uint128::new(amount),
+          msg: to_binary(&Cw20HookMsg::Sell {}).unwrap(),
+        })
+      };
+      let res = execute(deps.as_mut(), mock_env(), mock_info(TOKEN, &[]), sell(100)).unwrap();
+      assert!(res.attributes.contains(&attr("luna_sent", "408")));
+      let res: CurveResponse = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Curve {}).unwrap()).unwrap();
+      assert_eq!(Uint128::new(258), res.supply);
+
+      let err = execute(deps.as_mut(), mock_env(), mock_info(TOKEN, &[]), sell(259)).unwrap_err();
+      assert!(matches!(err, ContractError::CurveSupplyExceeded { .. }));
+    }
+
+    #[test]
+    fn try_presale_on_curve_cannot_sell_at_a_profit() {
+      let mut deps = mock_dependencies(&coins(1_000, "uluna"));
+      deps.querier.with_token_balances(&[(
+        &TOKEN.to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::new(1_000_000))],
+      )]);
+
+      let msg = InstantiateMsg {
+        oracle_address: None,
+        public_sale_time: Some(mock_env().block.time.seconds() + 100),
+        curve: Some(Curve::Linear { base_price: Decimal::one(), slope: Decimal::percent(1) }),
+        ..default_instantiate_msg()
+      };
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+      let msg = ExecuteMsg::UpdateAllowList {
+        add: vec![
+          AllowListEntry { address: String::from("alice"), tier: Tier { price: Some(Decimal::percent(50)), max_tokens: None } },
+          AllowListEntry { address: String::from("bob"), tier: Tier { price: None, max_tokens: None } },
+        ],
+        remove: vec![],
+      };
+      let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      let buy = ExecuteMsg::Buy { min_tokens_out: None, max_price: None, deadline: None };
+      let _res = execute(deps.as_mut(), mock_env(), mock_info("bob", &coins(1_000, "uluna")), buy.clone()).unwrap();
+
+      // alice's tier price does not undercut the curve, so selling straight back returns less than she paid
+      let res = execute(deps.as_mut(), mock_env(), mock_info("alice", &coins(1_000, "uluna")), buy).unwrap();
+      let tokens = res.attributes.iter().find(|a| a.key == "coins_sent").unwrap().value.parse::<u128>().unwrap();
+      assert!(tokens < 2_000);
+
+      let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: String::from("alice"),
+        amount: Uint128::new(tokens),
+        msg: to_binary(&Cw20HookMsg::Sell {}).unwrap(),
+      });
+      let res = execute(deps.as_mut(), mock_env(), mock_info(TOKEN, &[]), msg).unwrap();
+      let luna_sent = res.attributes.iter().find(|a| a.key == "luna_sent").unwrap().value.parse::<u128>().unwrap();
+      assert!(luna_sent <= 1_000);
+    }
+
+    #[test]
+    fn try_simulate_exponential_curve() {
+      let mut deps = mock_dependencies(&[]);
+      deps.querier.with_token_balances(&[(
+        &TOKEN.to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::new(1_000))],
+      )]);
+
+      let msg = InstantiateMsg {
+        oracle_address: None,
+        curve: Some(Curve::Exponential { base_price: Decimal::one(), growth: Decimal::one(), step: Uint128::new(10) }),
+        ..default_instantiate_msg()
+      };
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      // the price doubles every 10 tokens: 10 at 1 uluna, 10 at 2 and 10 at 4 cost 70 uluna
+      let msg = QueryMsg::Simulate { buyer: None, offer_amount: coin(75, "uluna") };
+      let res: SimulateResponse = from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+      assert_eq!(Uint128::new(30), res.tokens_out);
+      assert_eq!(coin(5, "uluna"), res.refund);
+
+      // nothing has been sold to sell back
+      let msg = QueryMsg::SimulateSell { amount: Uint128::new(1) };
+      assert!(query(deps.as_ref(), mock_env(), msg).is_err());
+    }
+
+    #[test]
+    fn try_buy_with_vesting() {
+      let mut deps = mock_dependencies(&[]);
+      deps.querier.with_oracle_price(Decimal::percent(1000));
+      deps.querier.with_token_balances(&[(
+        &TOKEN.to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::new(1_000))],
+      )]);
+
+      let msg = InstantiateMsg {
+        vesting: Some(Vesting { cliff: 100, duration: 1_000 }),
+        ..default_instantiate_msg()
+      };
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      // the tokens stay in the contract, and are no longer for sale
+      let msg = ExecuteMsg::Buy { min_tokens_out: None, max_price: None, deadline: None };
+      let res = execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(1_000, "uluna")), msg).unwrap();
+      assert!(res.messages.is_empty());
+      let res: InventoryResponse = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Inventory {}).unwrap()).unwrap();
+      assert_eq!(Uint128::new(900), res.balance);
+      let msg = ExecuteMsg::Withdraw { assets: vec![AssetUnchecked::cw20(TOKEN, 901u128)], recipient: None };
+      let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap_err();
+      assert!(matches!(err, ContractError::InsufficientTreasuryBalance { .. }));
+
+      let at = |seconds: u64| {
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(seconds);
+        env
+      };
+      let status = |deps: Deps, seconds: u64| -> VestingStatusResponse {
+        let msg = QueryMsg::VestingStatus { address: String::from("buyer") };
+        from_binary(&query(deps, at(seconds), msg).unwrap()).unwrap()
+      };
+
+      // nothing vests before the cliff
+      let err = execute(deps.as_mut(), at(50), mock_info("buyer", &[]), ExecuteMsg::Claim {}).unwrap_err();
+      assert!(matches!(err, ContractError::NothingToClaim {}));
+      assert_eq!(VestingStatusResponse { vested: Uint128::zero(), claimed: Uint128::zero(), locked: Uint128::new(100) }, status(deps.as_ref(), 50));
+
+      // halfway through, half of the tokens can be claimed
+      let res = execute(deps.as_mut(), at(500), mock_info("buyer", &[]), ExecuteMsg::Claim {}).unwrap();
+      assert_eq!(
+        CosmosMsg::Wasm(WasmMsg::Execute {
+          contract_addr: String::from(TOKEN),
+          msg: to_binary(&Cw20ExecuteMsg::Transfer { recipient: String::from("buyer"), amount: Uint128::new(50) }).unwrap(),
+          funds: vec![],
+        }),
+        res.messages[0].msg
+      );
+      assert_eq!(VestingStatusResponse { vested: Uint128::new(50), claimed: Uint128::new(50), locked: Uint128::new(50) }, status(deps.as_ref(), 500));
+
+      let res = execute(deps.as_mut(), at(2_000), mock_info("buyer", &[]), ExecuteMsg::Claim {}).unwrap();
+      assert!(res.attributes.contains(&attr("claimed", "50")));
+      assert_eq!(VestingStatusResponse { vested: Uint128::new(100), claimed: Uint128::new(100), locked: Uint128::zero() }, status(deps.as_ref(), 2_000));
 
+      let msg = ExecuteMsg::UpdateVesting { vesting: Some(Vesting { cliff: 10, duration: 5 }) };
+      let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap_err();
+      assert!(matches!(err, ContractError::InvalidVesting {}));
     }
 }
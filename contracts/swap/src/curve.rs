@@ -0,0 +1,177 @@
+use std::convert::TryInto;
+
+use cosmwasm_std::{Decimal, Fraction, StdError, StdResult, Uint128, Uint256};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use shared::math::{decimal_mul, decimal_pow, div_floor};
+
+// A bonding curve prices tokens from the supply the curve has sold, in uluna per token. Buying
+// moves up the curve and selling moves back down it, so both sides are priced by the area under
+// the curve between the old and the new supply.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Curve {
+    // every token costs `price`
+    Constant { price: Decimal },
+    // the price rises by `slope` with every token sold
+    Linear { base_price: Decimal, slope: Decimal },
+    // the price grows by `growth` (e.g. 0.01 for 1%) with every `step` tokens sold
+    Exponential { base_price: Decimal, growth: Decimal, step: Uint128 },
+}
+
+impl Curve {
+    // The first token must have a price, or a buy could take the whole inventory for nothing.
+    pub fn is_valid(&self) -> bool {
+        match self {
+            Curve::Constant { price } => !price.is_zero(),
+            Curve::Linear { base_price, .. } => !base_price.is_zero(),
+            Curve::Exponential { base_price, step, .. } => !base_price.is_zero() && !step.is_zero(),
+        }
+    }
+
+    // The same curve with every price multiplied by `rate`, e.g. to price it in another denom.
+    pub fn scale(&self, rate: Decimal) -> StdResult<Curve> {
+        Ok(match self {
+            Curve::Constant { price } => Curve::Constant { price: decimal_mul(*price, rate)? },
+            Curve::Linear { base_price, slope } => Curve::Linear {
+                base_price: decimal_mul(*base_price, rate)?,
+                slope: decimal_mul(*slope, rate)?,
+            },
+            Curve::Exponential { base_price, growth, step } => Curve::Exponential {
+                base_price: decimal_mul(*base_price, rate)?,
+                growth: *growth,
+                step: *step,
+            },
+        })
+    }
+
+    // The price of the next token once `supply` tokens are sold.
+    pub fn spot_price(&self, supply: Uint128) -> StdResult<Decimal> {
+        match self {
+            Curve::Constant { price } => Ok(*price),
+            Curve::Linear { base_price, slope } => {
+                let atomics = Uint256::from(base_price.numerator())
+                    .checked_add(Uint256::from(slope.numerator()).checked_mul(supply.into())?)?;
+                from_atomics(atomics)
+            }
+            Curve::Exponential { base_price, growth, step } => {
+                exponential_price(*base_price, *growth, (supply / *step).u128())
+            }
+        }
+    }
+
+    // What buying `amount` tokens costs once `supply` are sold, rounded up.
+    pub fn cost(&self, supply: Uint128, amount: Uint128) -> StdResult<Uint128> {
+        let area = self.area(supply, amount)?;
+        let one = Uint256::from(Decimal::one().denominator());
+        to_uint128(area.checked_add(one - Uint256::from(1u8))? / one)
+    }
+
+    // What selling `amount` tokens back pays once `supply` are sold, rounded down.
+    pub fn proceeds(&self, supply: Uint128, amount: Uint128) -> StdResult<Uint128> {
+        let start = supply
+            .checked_sub(amount)
+            .map_err(|_| StdError::generic_err("Cannot sell more than the curve supply"))?;
+        let area = self.area(start, amount)?;
+        to_uint128(area / Uint256::from(Decimal::one().denominator()))
+    }
+
+    // The most tokens `payment` buys once `supply` are sold.
+    pub fn tokens_for(&self, supply: Uint128, payment: Uint128) -> StdResult<Uint128> {
+        let spot_price = self.spot_price(supply)?;
+        if spot_price.is_zero() {
+            return Err(StdError::generic_err("Bonding curve price must be positive"));
+        }
+        if let Curve::Constant { price } = self {
+            return Ok(div_floor(payment, *price));
+        }
+
+        // the price never falls, so the spot price bounds how many tokens the payment can buy
+        let mut low = Uint128::zero();
+        let bound = payment.full_mul(spot_price.denominator()) / Uint256::from(spot_price.numerator());
+        let mut high = to_uint128(bound).unwrap_or(Uint128::MAX);
+
+        while low < high {
+            let mid = low + (high - low + Uint128::new(1)) / Uint128::new(2);
+            if matches!(self.cost(supply, mid), Ok(cost) if cost <= payment) {
+                low = mid;
+            } else {
+                high = mid - Uint128::new(1);
+            }
+        }
+        Ok(low)
+    }
+
+    // The area under the curve from `supply` to `supply + amount`, in atomics of a Decimal.
+    fn area(&self, supply: Uint128, amount: Uint128) -> StdResult<Uint256> {
+        let amount_256 = Uint256::from(amount);
+        match self {
+            Curve::Constant { price } => Ok(Uint256::from(price.numerator()).checked_mul(amount_256)?),
+            Curve::Linear { base_price, slope } => {
+                // base_price * amount + slope * ((supply + amount)^2 - supply^2) / 2
+                let base = Uint256::from(base_price.numerator()).checked_mul(amount_256)?;
+                let span = Uint256::from(supply)
+                    .checked_mul(Uint256::from(2u8))?
+                    .checked_add(amount_256)?
+                    .checked_mul(amount_256)?;
+                let rise = Uint256::from(slope.numerator()).checked_mul(span)? / Uint256::from(2u8);
+                Ok(base.checked_add(rise)?)
+            }
+            Curve::Exponential { base_price, growth, step } => {
+                let end = supply.checked_add(amount)?;
+                let (first_step, last_step) = ((supply / *step).u128(), (end / *step).u128());
+                let price_at = |k: u128| -> StdResult<Uint256> {
+                    Ok(Uint256::from(exponential_price(*base_price, *growth, k)?.numerator()))
+                };
+
+                if first_step == last_step {
+                    return Ok(price_at(first_step)?.checked_mul(amount_256)?);
+                }
+
+                // the partial steps at either end, and the whole steps between them
+                let head = Uint128::new(first_step + 1) * *step - supply;
+                let tail = end - Uint128::new(last_step) * *step;
+                let whole = Uint256::from(*step)
+                    .checked_mul(geometric_sum(*base_price, *growth, first_step + 1, last_step)?)?;
+                Ok(price_at(first_step)?
+                    .checked_mul(head.into())?
+                    .checked_add(whole)?
+                    .checked_add(price_at(last_step)?.checked_mul(tail.into())?)?)
+            }
+        }
+    }
+}
+
+// base_price * (1 + growth)^k
+fn exponential_price(base_price: Decimal, growth: Decimal, k: u128) -> StdResult<Decimal> {
+    decimal_mul(base_price, decimal_pow(Decimal::one() + growth, k)?)
+}
+
+// The sum of the exponential prices of steps `from` up to (not including) `to`, in atomics.
+fn geometric_sum(base_price: Decimal, growth: Decimal, from: u128, to: u128) -> StdResult<Uint256> {
+    if to <= from {
+        return Ok(Uint256::zero());
+    }
+    if growth.is_zero() {
+        return Ok(Uint256::from(base_price.numerator()).checked_mul(Uint256::from(to - from))?);
+    }
+
+    // (price_to - price_from) / growth
+    let first = Uint256::from(exponential_price(base_price, growth, from)?.numerator());
+    let last = Uint256::from(exponential_price(base_price, growth, to)?.numerator());
+    Ok(last
+        .checked_sub(first)?
+        .checked_mul(Uint256::from(growth.denominator()))?
+        / Uint256::from(growth.numerator()))
+}
+
+fn from_atomics(atomics: Uint256) -> StdResult<Decimal> {
+    Ok(Decimal::from_ratio(to_uint128(atomics)?, Decimal::one().denominator()))
+}
+
+fn to_uint128(value: Uint256) -> StdResult<Uint128> {
+    value
+        .try_into()
+        .map_err(|_| StdError::generic_err("Bonding curve overflow"))
+}
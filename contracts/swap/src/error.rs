@@ -1,4 +1,4 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{Decimal, StdError, Uint128};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -16,9 +16,78 @@ pub enum ContractError {
     #[error("Not implemented")]
     NotImplemented {},
 
-    #[error("Only uluna should be passed")]
+    #[error("Pay with uluna or an accepted denom")]
     CoinMismatch {},
 
+    #[error("Denom {denom} is not accepted")]
+    UnsupportedDenom { denom: String },
+
     #[error("Not enough coins remain in contract")]
     InsufficientCoinsInContract {},
+
+    #[error("Oracle price must be positive")]
+    InvalidPrice {},
+
+    #[error("Oracle price is stale: last updated {age} seconds ago, max age is {max_age}")]
+    StalePrice { age: u64, max_age: u64 },
+
+    #[error("Oracle price feed is paused")]
+    PriceFeedPaused {},
+
+    #[error("Deadline {deadline} has passed")]
+    DeadlineExceeded { deadline: u64 },
+
+    #[error("Price {price} is above the maximum of {max_price}")]
+    MaxPriceExceeded { price: Decimal, max_price: Decimal },
+
+    #[error("{tokens_out} tokens out is below the minimum of {min_tokens_out}")]
+    MinTokensOutNotMet { tokens_out: Uint128, min_tokens_out: Uint128 },
+
+    #[error("Either an oracle or a bonding curve is needed to price tokens")]
+    NoPriceSource {},
+
+    #[error("Bonding curve prices must be positive")]
+    InvalidCurve {},
+
+    #[error("Cannot sell {amount} tokens back to a curve that has sold {supply}")]
+    CurveSupplyExceeded { amount: Uint128, supply: Uint128 },
+
+    #[error("Vesting cliff must not be longer than its duration")]
+    InvalidVesting {},
+
+    #[error("Nothing to claim")]
+    NothingToClaim {},
+
+    #[error("Spread must be less than 1")]
+    InvalidSpread {},
+
+    #[error("Fee must be less than 10000 bps")]
+    InvalidFee {},
+
+    #[error("Not enough {asset} in treasury: {requested} requested, {available} available")]
+    InsufficientTreasuryBalance { asset: String, requested: Uint128, available: Uint128 },
+
+    #[error("Sale starts at {start_time}")]
+    SaleNotStarted { start_time: u64 },
+
+    #[error("Sale ended at {end_time}")]
+    SaleEnded { end_time: u64 },
+
+    #[error("Sale must end after it starts")]
+    InvalidSaleWindow {},
+
+    #[error("{requested} tokens exceed the buyer's remaining allowance of {remaining}")]
+    BuyerLimitExceeded { requested: Uint128, remaining: Uint128 },
+
+    #[error("{address} is not allow-listed before the public sale at {public_sale_time}")]
+    NotAllowListed { address: String, public_sale_time: u64 },
+
+    #[error("{requested} tokens exceed the tier's remaining allowance of {remaining}")]
+    TierLimitExceeded { requested: Uint128, remaining: Uint128 },
+
+    #[error("{requested} tokens exceed the {remaining} left in the sale")]
+    SaleCapExceeded { requested: Uint128, remaining: Uint128 },
+
+    #[error("Not enough uluna in contract: {required} required, {available} available")]
+    InsufficientLuna { required: Uint128, available: Uint128 },
 }
@@ -1,30 +1,195 @@
-use cosmwasm_std::Addr;
+use cosmwasm_std::{Addr, Coin, Decimal, Uint128};
+use cw20::Cw20ReceiveMsg;
+use cw_asset::AssetUnchecked;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+pub use crate::curve::Curve;
+pub use crate::state::{SaleLimits, Tier, Vesting};
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct InstantiateMsg {
     pub token_address: Addr,
-    pub oracle_address: Addr,
+    // optional when the contract runs on a bonding curve
+    pub oracle_address: Option<Addr>,
+    pub max_price_age: Option<u64>,
+    // fraction of the oracle value kept by the contract when buying Lemons back, e.g. 0.01 for 1%
+    pub spread: Option<Decimal>,
+    // fee charged on buys, in basis points of the uluna paid
+    pub fee_bps: Option<u16>,
+    // defaults to the owner
+    pub fee_recipient: Option<Addr>,
+    // native denoms Buy accepts besides uluna, e.g. uusd
+    pub accepted_denoms: Option<Vec<String>>,
+    pub sale_limits: Option<SaleLimits>,
+    // starts the sale in presale mode, open to allow-listed buyers only until this time
+    pub public_sale_time: Option<u64>,
+    // prices tokens along a bonding curve instead of at the oracle price
+    pub curve: Option<Curve>,
+    pub vesting: Option<Vesting>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct AllowListEntry {
+    pub address: String,
+    pub tier: Tier,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ExecuteMsg {
-    Buy {},
-    Withdraw { amount: i32 },
+    // Buy takes uluna or any accepted denom, converted at market rates. It reverts unless at least
+    // `min_tokens_out` are bought, at no more than `max_price` (in uluna), before `deadline`
+    Buy { min_tokens_out: Option<Uint128>, max_price: Option<Decimal>, deadline: Option<u64> },
+    // Withdraw sends treasury assets, native or CW20, to the recipient (the owner by default)
+    Withdraw { assets: Vec<AssetUnchecked>, recipient: Option<String> },
+    // WithdrawAll sends every native balance and all Lemons held; other CW20s have to be listed in Withdraw
+    WithdrawAll { recipient: Option<String> },
+    // Receive handles Lemons sent through the token contract, see Cw20HookMsg
+    Receive(Cw20ReceiveMsg),
+    UpdateSpread { spread: Decimal },
+    UpdateFeeConfig { fee_bps: u16, fee_recipient: Addr },
+    // WithdrawFees sends the accumulated fees to the fee recipient
+    WithdrawFees {},
+    UpdateAcceptedDenoms { denoms: Vec<String> },
+    UpdateSaleLimits { sale_limits: SaleLimits },
+    // UpdatePresale sets the public sale time, None ending the presale
+    UpdatePresale { public_sale_time: Option<u64> },
+    // UpdateAllowList adds buyers or changes their tier, and removes others from the presale
+    UpdateAllowList { add: Vec<AllowListEntry>, remove: Vec<String> },
+    // UpdateVesting changes the vesting of future purchases, None sending tokens right away
+    UpdateVesting { vesting: Option<Vesting> },
+    // Claim sends the sender every vested token not claimed yet
+    Claim {},
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Cw20HookMsg {
+    // Sell pays out uluna for the Lemons sent, at the oracle or curve price minus the spread
+    Sell {},
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
     QueryPrice {},
-    Balance { address: Addr },
+    Config {},
+    // Simulate returns what a Buy paying `offer_amount` would get at the current price. With a
+    // buyer it applies their presale tier and purchase limits, and without one it quotes a first
+    // purchase at the public price.
+    Simulate { offer_amount: Coin, buyer: Option<String> },
+    // SimulateSell returns what selling `amount` Lemons would pay out at the current price
+    SimulateSell { amount: Uint128 },
+    // Curve returns the bonding curve and the supply it has sold
+    Curve {},
+    // Inventory returns the Lemons held for sale
+    Inventory {},
+    // Treasury returns the native balances, leaving out uncollected fees
+    Treasury {},
+    Fees {},
+    AcceptedDenoms {},
+    // Allowance returns how many more tokens an address can buy under the sale limits
+    Allowance { address: String },
+    AllowList { address: String },
+    VestingStatus { address: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ConfigResponse {
+    pub owner: Addr,
+    pub token_address: Addr,
+    pub oracle_address: Option<Addr>,
+    pub max_price_age: Option<u64>,
+    pub spread: Decimal,
+    pub fee_bps: u16,
+    pub fee_recipient: Addr,
+    pub sale_limits: SaleLimits,
+    pub public_sale_time: Option<u64>,
+    pub curve: Option<Curve>,
+    pub vesting: Option<Vesting>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct VestingStatusResponse {
+    // tokens vested so far, claimed or not
+    pub vested: Uint128,
+    pub claimed: Uint128,
+    // tokens still vesting
+    pub locked: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct CurveResponse {
+    // None when prices come from the oracle
+    pub curve: Option<Curve>,
+    pub supply: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct AllowanceResponse {
+    pub purchased: Uint128,
+    // the lowest of the buyer's, their tier's and the sale's remaining tokens, None when none is capped
+    pub remaining: Option<Uint128>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct AllowListResponse {
+    // None when the address isn't allow-listed
+    pub tier: Option<Tier>,
+    pub public_sale_time: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct SimulateResponse {
+    pub tokens_out: Uint128,
+    // part of the offer kept as fee
+    pub fee: Coin,
+    // part of the offer left over after paying for whole tokens
+    pub refund: Coin,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct SimulateSellResponse {
+    // uluna paid out to the seller
+    pub return_amount: Uint128,
+    pub spread_amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct InventoryResponse {
+    pub token_address: Addr,
+    pub balance: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct TreasuryResponse {
+    pub balances: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct FeesResponse {
+    pub fee_bps: u16,
+    pub fee_recipient: Addr,
+    // fees collected in each denom and not yet withdrawn
+    pub accumulated_fees: Vec<Coin>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
-pub struct PriceResponse {
-  pub price: u64
+pub struct AcceptedDenomsResponse {
+    // always includes uluna
+    pub denoms: Vec<String>,
 }
\ No newline at end of file
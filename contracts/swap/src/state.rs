@@ -1,14 +1,95 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::Addr;
-use cw_storage_plus::Item;
+use cosmwasm_std::{Addr, Decimal, Uint128};
+use cw_storage_plus::{Item, Map, U64Key};
+
+use crate::curve::Curve;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct State {
     pub owner: Addr,
     pub token_address: Addr,
-    pub oracle_address: Addr
+    // prices buys and sells unless the contract runs on a bonding curve
+    pub oracle_address: Option<Addr>,
+    // oracle prices older than this (in seconds) are rejected
+    pub max_price_age: Option<u64>,
+    // fraction of the oracle value kept on sells
+    pub spread: Decimal,
+    // fee charged on buys, in basis points
+    pub fee_bps: u16,
+    pub fee_recipient: Addr,
+    // stable denoms accepted by Buy besides uluna, priced at market rates
+    pub accepted_denoms: Vec<String>,
+    pub sale_limits: SaleLimits,
+    // until this block time (in seconds) only allow-listed buyers can buy, None for no presale
+    pub public_sale_time: Option<u64>,
+    // prices buys and sells from the curve supply instead of the oracle
+    pub curve: Option<Curve>,
+    // locks bought tokens in the contract until they vest, None to transfer them right away
+    pub vesting: Option<Vesting>,
+}
+
+// Bought tokens stay locked until `cliff` seconds after the purchase, then vest linearly until
+// `duration` seconds after it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Vesting {
+    pub cliff: u64,
+    pub duration: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VestingRecord {
+    pub amount: Uint128,
+    pub claimed: Uint128,
+    pub start_time: u64,
+    pub cliff_time: u64,
+    pub end_time: u64,
+}
+
+// Caps on how many tokens Buy sells, and when. Every limit is optional.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct SaleLimits {
+    pub max_tokens_per_buyer: Option<Uint128>,
+    // total tokens sold over the whole sale
+    pub max_tokens_sold: Option<Uint128>,
+    // block times (in seconds) the sale opens and closes at
+    pub start_time: Option<u64>,
+    pub end_time: Option<u64>,
 }
 
 pub const STATE: Item<State> = Item::new("state");
+
+// Presale terms for an allow-listed buyer.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Tier {
+    // fixed price in uluna per token, instead of the oracle price. Buys on a bonding curve are
+    // always priced on the curve.
+    pub price: Option<Decimal>,
+    // tokens the buyer can buy during the presale
+    pub max_tokens: Option<Uint128>,
+}
+
+// buyers approved for the presale
+pub const ALLOW_LIST: Map<&Addr, Tier> = Map::new("allow_list");
+
+// tokens sold along the bonding curve, net of the tokens sold back
+pub const CURVE_SUPPLY: Item<Uint128> = Item::new("curve_supply");
+
+// tokens vesting for each buyer, one record per purchase, keyed by buyer and record id
+pub const VESTING_RECORDS: Map<(&Addr, U64Key), VestingRecord> = Map::new("vesting_records");
+
+// last id handed out to a vesting record
+pub const VESTING_COUNT: Item<u64> = Item::new("vesting_count");
+
+// tokens held for buyers that haven't claimed them yet, which are no longer for sale
+pub const TOKENS_LOCKED: Item<Uint128> = Item::new("tokens_locked");
+
+// tokens bought by each buyer
+pub const PURCHASES: Map<&Addr, Uint128> = Map::new("purchases");
+
+// tokens bought by everyone
+pub const TOKENS_SOLD: Item<Uint128> = Item::new("tokens_sold");
+
+// fees collected per denom, kept apart from the principal until the fee recipient withdraws them
+pub const ACCUMULATED_FEES: Map<&str, Uint128> = Map::new("accumulated_fees");
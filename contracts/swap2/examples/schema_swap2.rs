@@ -3,7 +3,10 @@ use std::fs::create_dir_all;
 
 use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
 
-use swap2::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
+use swap2::msg::{
+    Cw20HookMsg, DelegationsResponse, ExecuteMsg, InstantiateMsg, LiquidStakingResponse, QueryMsg,
+    UnbondingQueueResponse, UnbondingResponse,
+};
 
 fn main() {
     let mut out_dir = current_dir().unwrap();
@@ -14,4 +17,9 @@ fn main() {
     export_schema(&schema_for!(InstantiateMsg), &out_dir);
     export_schema(&schema_for!(ExecuteMsg), &out_dir);
     export_schema(&schema_for!(QueryMsg), &out_dir);
+    export_schema(&schema_for!(Cw20HookMsg), &out_dir);
+    export_schema(&schema_for!(DelegationsResponse), &out_dir);
+    export_schema(&schema_for!(UnbondingQueueResponse), &out_dir);
+    export_schema(&schema_for!(UnbondingResponse), &out_dir);
+    export_schema(&schema_for!(LiquidStakingResponse), &out_dir);
 }
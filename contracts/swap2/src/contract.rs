@@ -1,26 +1,40 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    Binary, Deps, DepsMut, Empty, Env, MessageInfo, Response, StakingMsg,
-    StdResult, attr, to_binary, Addr, Uint128,
+    Binary, Decimal, Deps, DepsMut, Empty, Env, MessageInfo, Response, StakingMsg,
+    StdResult, attr, from_binary, to_binary, Addr, Uint128,
     CosmosMsg, WasmMsg, BankMsg, coin, Coin,
-    DistributionMsg, SubMsg
+    DistributionMsg, Order, Reply, StdError, SubMsg
 };
 
-use cw20::{BalanceResponse, Cw20ExecuteMsg};
+use cw20::{BalanceResponse, Cw20ExecuteMsg, Cw20ReceiveMsg};
 use cw2::set_contract_version;
+use cw_asset::AssetInfo;
+use cw_storage_plus::{Bound, U64Key};
 
 use crate::error::ContractError;
-use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg, QueryTokenAddressResponse};
-use crate::state::{STATE, State};
-use shared::oracle::PriceResponse;
-use terra_cosmwasm::{create_swap_msg, TerraQuerier, ExchangeRatesResponse, TerraMsgWrapper, TerraMsg};
+use crate::msg::{
+    Cw20HookMsg, DelegationsResponse, ExecuteMsg, InstantiateMsg, LiquidStakingResponse, QueryMsg,
+    QueryTokenAddressResponse, UnbondingQueueResponse, UnbondingResponse, ValidatorDelegation,
+};
+use crate::state::{
+    DELEGATION_SHARES, LIQUID_STAKING, PENDING_WITHDRAW, PendingWithdraw, STATE, State, UNBONDING_COUNT, UNBONDING_QUEUE, UNBONDING_RECEIVED,
+    UnbondingEntry, ValidatorWeight,
+};
+use shared::math::{div_floor, mul_ceil};
+use shared::oracle::{Pair, PriceResponse, PriceStatus, QueryMsg as OracleQueryMsg};
+use terra_cosmwasm::{create_swap_msg, TerraQuerier, ExchangeRatesResponse, TerraMsgWrapper};
 
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:swap";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
-const VALIDATOR: &str = "terravaloper1vk20anceu6h9s00d27pjlvslz3avetkvnwmr35";
+const WITHDRAW_REWARDS_REPLY_ID: u64 = 1;
+const SWAP_REWARDS_REPLY_ID: u64 = 2;
+// undelegated luna is returned after 21 days
+const UNBONDING_PERIOD: u64 = 21 * 24 * 60 * 60;
+const MAX_LIMIT: u32 = 30;
+const DEFAULT_LIMIT: u32 = 10;
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
@@ -30,11 +44,15 @@ pub fn instantiate(
     msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    validate_validators(deps.as_ref(), &msg.validators)?;
 
     let initial_state = State {
       owner: info.sender,
       token_address: msg.token_address,
       oracle_address: msg.oracle_address,
+      max_price_age: msg.max_price_age,
+      validators: msg.validators,
+      receipt_token: msg.receipt_token,
     };
 
     STATE.save(deps.storage, &initial_state)?;
@@ -53,185 +71,835 @@ pub fn execute(
     msg: ExecuteMsg,
 ) -> Result<Response<TerraMsgWrapper>, ContractError> {
     match msg {
-      ExecuteMsg::Buy {} => try_buy(deps, env, info, msg),
-      ExecuteMsg::Withdraw { amount } => try_start_withdraw(deps, env, info, amount),
-      ExecuteMsg::WithdrawStep2ConvertRewardsToLuna { amount } => try_convert_rewards(deps, env, info, amount),
-      ExecuteMsg::WithdrawStep3SendLuna { amount } => try_send_luna(deps, env, info, amount),
+      ExecuteMsg::Buy { min_tokens_out, max_price, deadline } => {
+        try_buy(deps, env, info, min_tokens_out, max_price, deadline)
+      }
+      ExecuteMsg::Withdraw {} => try_withdraw(deps, env, info),
       ExecuteMsg::StartUndelegation { amount } => try_start_undelegation(deps, env, info, amount),
+      ExecuteMsg::ClaimUnbonded {} => try_claim_unbonded(deps, env, info),
+      ExecuteMsg::UpdateValidators { validators } => try_update_validators(deps, info, validators),
+      ExecuteMsg::Redelegate { src_validator, dst_validator, amount } => {
+        try_redelegate(deps, env, info, src_validator, dst_validator, amount)
+      }
+      ExecuteMsg::Rebalance {} => try_rebalance(deps, env, info),
+      ExecuteMsg::Deposit {} => try_deposit(deps, env, info),
+      ExecuteMsg::Receive(cw20_msg) => try_receive(deps, env, info, cw20_msg),
     }
 }
 
-pub fn try_buy(deps: DepsMut, env: Env, info: MessageInfo, _msg: ExecuteMsg) -> Result<Response<TerraMsgWrapper>, ContractError> {
-  let price_in_luna = get_price(deps.as_ref())?.price as u128;
+pub fn try_buy(
+  deps: DepsMut,
+  env: Env,
+  info: MessageInfo,
+  min_tokens_out: Option<Uint128>,
+  max_price: Option<Decimal>,
+  deadline: Option<u64>,
+) -> Result<Response<TerraMsgWrapper>, ContractError> {
+  if let Some(deadline) = deadline {
+    if env.block.time.seconds() > deadline {
+      return Err(ContractError::DeadlineExceeded { deadline });
+    }
+  }
+
+  let price_in_luna = get_fresh_price(deps.as_ref(), &env)?.price;
+  if price_in_luna.is_zero() {
+    return Err(ContractError::InvalidPrice {});
+  }
+  if let Some(max_price) = max_price {
+    if price_in_luna > max_price {
+      return Err(ContractError::MaxPriceExceeded { price: price_in_luna, max_price });
+    }
+  }
 
-  if info.funds.len() == 0 {
+  if info.funds.is_empty() {
       return Err(ContractError::CoinMismatch {})
   }
+  // the luna paid is delegated, so no other denom can be taken
+  if let Some(other) = info.funds.iter().find(|c| c.denom != "uluna") {
+    return Err(ContractError::UnsupportedDenom { denom: other.denom.clone() });
+  }
 
   let luna_received: Uint128 = info
     .funds
     .iter()
-    .find(|c| c.denom == String::from("uluna"))
-    .map(|c| Uint128::from(c.amount))
+    .find(|c| c.denom == "uluna")
+    .map(|c| c.amount)
     .unwrap_or_else(Uint128::zero);
 
-  let coins_to_be_sent = luna_received.u128() / price_in_luna;
-  let coins_in_contract = get_balance_of_cw20(deps.as_ref(), env.contract.address)?.balance.u128();
+  // Tokens are rounded down and their cost is rounded up, so nothing is ever sold below the
+  // oracle price. The uluna left over after paying for whole tokens is refunded to the buyer.
+  let coins_to_be_sent = div_floor(luna_received, price_in_luna);
+  if coins_to_be_sent.is_zero() {
+    return Err(ContractError::InvalidQuantity {});
+  }
+  if let Some(min_tokens_out) = min_tokens_out {
+    if coins_to_be_sent < min_tokens_out {
+      return Err(ContractError::MinTokensOutNotMet { tokens_out: coins_to_be_sent, min_tokens_out });
+    }
+  }
+  let luna_spent = mul_ceil(coins_to_be_sent, price_in_luna);
+  let luna_refunded = luna_received - luna_spent;
+
+  // the luna delegated for purchases is the owner's part of the delegations
+  let (total_delegated, total_shares) = delegation_shares(deps.as_ref(), &env)?;
+  DELEGATION_SHARES.save(deps.storage, &(total_shares + shares_for(luna_spent, total_delegated, total_shares)))?;
+
+  let coins_in_contract = get_balance_of_cw20(deps.as_ref(), env.contract.address)?.balance;
 
   if coins_in_contract < coins_to_be_sent { return Err(ContractError::InsufficientCoinsInContract {}) }
 
-  let token_addr = STATE.load(deps.storage)?.token_address;
+  let state = STATE.load(deps.storage)?;
 
   let msg_transfer = CosmosMsg::Wasm(WasmMsg::Execute {
-      contract_addr: token_addr.to_string(),
+      contract_addr: state.token_address.to_string(),
       funds: vec![],
       msg: to_binary(&Cw20ExecuteMsg::Transfer {
           recipient: info.sender.to_string(),
-          amount: Uint128::from(coins_to_be_sent),
+          amount: coins_to_be_sent,
       })?,
   });
 
-  let msg_delegate = CosmosMsg::Staking(StakingMsg::Delegate {
-      validator: String::from(VALIDATOR),
-      amount: Coin {
-          denom: String::from("uluna"),
-          amount: luna_received,
-      },
-  });
+  let msgs_delegate = delegate_msgs(&state.validators, luna_spent);
 
-  Ok(Response::new().add_attributes(
+  let mut response = Response::new().add_attributes(
     vec![
         ("price", price_in_luna.to_string()),
         ("luna_received", luna_received.to_string()),
         ("coins_sent", coins_to_be_sent.to_string()),
+        ("luna_refunded", luna_refunded.to_string()),
       ]
-    ).add_messages(vec![msg_transfer, msg_delegate])
-  )
+    ).add_message(msg_transfer)
+    .add_messages(msgs_delegate);
+
+  if !luna_refunded.is_zero() {
+    response = response.add_message(CosmosMsg::Bank(BankMsg::Send {
+      to_address: info.sender.to_string(),
+      amount: vec![coin(luna_refunded.u128(), "uluna")],
+    }));
+  }
+
+  Ok(response)
 }
 
-pub fn try_start_withdraw(deps: DepsMut, env: Env, info: MessageInfo, amount: u64) -> Result<Response<TerraMsgWrapper>, ContractError> {
+pub fn try_withdraw(mut deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response<TerraMsgWrapper>, ContractError> {
   let owner = STATE.load(deps.storage)?.owner;
   if info.sender != owner {
     return Err(ContractError::Unauthorized {});
   }
 
-  // Create a list of submessages that will execute in a series
-  let mut submessages: Vec<SubMsg<TerraMsgWrapper>> = vec![];
-  // Step 1: Claim rewards
-  submessages.push(SubMsg::new(CosmosMsg::Distribution(
-    DistributionMsg::WithdrawDelegatorReward {
-      validator: String::from(VALIDATOR),
-    }
-  )));
+  // luna returned by unbonding is set aside for its recipients before anything is counted as rewards
+  settle_unbonding(deps.branch(), &env)?;
 
-  // Step 2: Convert rewards
-  submessages.push(SubMsg::new(CosmosMsg::Wasm(
-    WasmMsg::Execute {
-      contract_addr: env.contract.address.to_string(),
-      msg: to_binary(&ExecuteMsg::WithdrawStep2ConvertRewardsToLuna { amount })?,
-      funds: vec![],
-    }
-  )));
+  // Step 1: Claim rewards from every validator holding a delegation, including any that
+  // have left the set since
+  let delegations = deps.querier.query_all_delegations(&env.contract.address)?;
+  let total_delegated: Uint128 = delegations.iter().map(|d| d.amount.amount).sum();
+  let pending = PendingWithdraw {
+    luna_rewards: Uint128::zero(),
+    luna_before_swaps: Uint128::zero(),
+    pool_shares: LIQUID_STAKING.may_load(deps.storage)?.unwrap_or_default().shares,
+    total_shares: DELEGATION_SHARES.may_load(deps.storage)?.unwrap_or_default(),
+    total_delegated,
+  };
+  PENDING_WITHDRAW.save(deps.storage, &pending)?;
 
-  // Step 3: Send luna to owner
-  submessages.push(SubMsg::new(CosmosMsg::Wasm(
-    WasmMsg::Execute {
-      contract_addr: env.contract.address.to_string(),
-      msg: to_binary(&ExecuteMsg::WithdrawStep3SendLuna { amount })?,
-      funds: vec![],
-    }
-  )));
+  // with nothing delegated there is nothing to claim, but rewards paid out earlier may be left
+  if delegations.is_empty() {
+    return try_convert_rewards(deps, env);
+  }
+  let last = delegations.len() - 1;
+  let submessages: Vec<SubMsg<TerraMsgWrapper>> = delegations
+    .into_iter()
+    .enumerate()
+    .map(|(i, delegation)| {
+      let msg = CosmosMsg::Distribution(DistributionMsg::WithdrawDelegatorReward { validator: delegation.validator });
+      // the reply comes once every claim has gone through
+      if i == last { SubMsg::reply_on_success(msg, WITHDRAW_REWARDS_REPLY_ID) } else { SubMsg::new(msg) }
+    })
+    .collect();
 
   Ok(Response::new()
     .add_attribute("method", "try_withdraw")
     .add_submessages(submessages))
 }
 
-pub fn try_convert_rewards(deps: DepsMut, env: Env, info: MessageInfo, amount: u64) -> Result<Response<TerraMsgWrapper>, ContractError> {
-  // Find all native denoms for which we have a balance.
-  let balances = deps.querier.query_all_balances(&env.contract.address)?;
-  let denoms: Vec<String> = balances.iter().map(|item| item.denom.clone()).collect();
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> Result<Response<TerraMsgWrapper>, ContractError> {
+  match msg.id {
+    WITHDRAW_REWARDS_REPLY_ID => try_convert_rewards(deps, env),
+    SWAP_REWARDS_REPLY_ID => try_send_luna(deps, env),
+    id => Err(ContractError::UnknownReplyId { id }),
+  }
+}
 
+// Step 2: Swap the rewards received in other denoms to luna
+fn try_convert_rewards(deps: DepsMut, env: Env) -> Result<Response<TerraMsgWrapper>, ContractError> {
+  let mut pending = PENDING_WITHDRAW.load(deps.storage)?;
   let reward_denom = String::from("uluna");
-  let exchange_rates = query_exchange_rates(&deps, reward_denom, denoms)?;
-
-  let mut submessages: Vec<SubMsg<TerraMsgWrapper>> = vec![];
-  for coin in balances {
-      if coin.denom == reward_denom
-          || !exchange_rates
-              .exchange_rates
-              .iter()
-              .any(|x| x.quote_denom == coin.denom)
-      {
-          // ignore luna and any other denom that's not convertible to luna.
-          continue;
-      }
 
-      // QUESTION: What's the difference between doing a Msg vs. a SubMsg?
-      submessages.push(SubMsg::new(create_swap_msg(coin, reward_denom.to_string())));
+  // Everything in the balance except the luna held for unbonding claims is rewards, including the
+  // rewards the chain pays out whenever a delegation changes.
+  let luna_held = UNBONDING_RECEIVED.may_load(deps.storage)?.unwrap_or_default();
+  let balances = deps.querier.query_all_balances(&env.contract.address)?;
+  let rewards: Vec<Coin> = balances
+    .iter()
+    .filter_map(|balance| {
+      let amount = if balance.denom == reward_denom { balance.amount.saturating_sub(luna_held) } else { balance.amount };
+      (!amount.is_zero()).then(|| coin(amount.u128(), balance.denom.clone()))
+    })
+    .collect();
+  if rewards.is_empty() {
+    return Err(ContractError::NothingToWithdraw {});
+  }
+
+  pending.luna_rewards = rewards.iter().find(|c| c.denom == reward_denom).map(|c| c.amount).unwrap_or_default();
+  pending.luna_before_swaps = balances.iter().find(|c| c.denom == reward_denom).map(|c| c.amount).unwrap_or_default();
+
+  let other_denoms: Vec<String> = rewards.iter().filter(|c| c.denom != reward_denom).map(|c| c.denom.clone()).collect();
+  let exchange_rates = if other_denoms.is_empty() {
+    vec![]
+  } else {
+    query_exchange_rates(&deps, reward_denom.clone(), other_denoms)?.exchange_rates
+  };
+  // ignore luna and any other denom that's not convertible to luna
+  let swaps: Vec<CosmosMsg<TerraMsgWrapper>> = rewards
+    .iter()
+    .filter(|c| exchange_rates.iter().any(|x| x.quote_denom == c.denom))
+    .map(|c| create_swap_msg(c.clone(), reward_denom.clone()))
+    .collect();
+
+  let rewards: Vec<String> = rewards.iter().map(|c| c.to_string()).collect();
+  let response = Response::new().add_attributes(vec![
+    ("method", "try_convert_rewards"),
+    ("rewards", &rewards.join(",")),
+  ]);
+
+  if swaps.is_empty() {
+    PENDING_WITHDRAW.remove(deps.storage);
+    let luna_balance = pending.luna_before_swaps;
+    return send_rewards(deps, response, &pending, luna_balance);
+  }
+
+  PENDING_WITHDRAW.save(deps.storage, &pending)?;
+  let last = swaps.len() - 1;
+  Ok(response.add_submessages(swaps.into_iter().enumerate().map(|(i, msg)| {
+    if i == last { SubMsg::reply_on_success(msg, SWAP_REWARDS_REPLY_ID) } else { SubMsg::new(msg) }
+  })))
+}
+
+// Step 3: Send the luna received to the owner
+fn try_send_luna(deps: DepsMut, env: Env) -> Result<Response<TerraMsgWrapper>, ContractError> {
+  let pending = PENDING_WITHDRAW.load(deps.storage)?;
+  PENDING_WITHDRAW.remove(deps.storage);
+
+  let luna_balance = deps.querier.query_balance(&env.contract.address, "uluna")?.amount;
+  send_rewards(deps, Response::new().add_attribute("method", "try_send_luna"), &pending, luna_balance)
+}
+
+// Splits the luna claimed directly plus whatever the swaps added to the balance since. The
+// liquid-staking pool's share is restaked, which raises the receipt exchange rate, and the rest
+// is sent to the owner.
+fn send_rewards(
+  deps: DepsMut,
+  response: Response<TerraMsgWrapper>,
+  pending: &PendingWithdraw,
+  luna_balance: Uint128,
+) -> Result<Response<TerraMsgWrapper>, ContractError> {
+  let state = STATE.load(deps.storage)?;
+  let luna_swapped = luna_balance.saturating_sub(pending.luna_before_swaps);
+  let amount = pending.luna_rewards + luna_swapped;
+  let restaked = if pending.total_shares.is_zero() {
+    Uint128::zero()
+  } else {
+    amount.multiply_ratio(pending.pool_shares, pending.total_shares)
+  };
+  let sent = amount - restaked;
+
+  let mut response = response.add_attributes(vec![
+    ("luna_rewards", pending.luna_rewards.to_string()),
+    ("luna_swapped", luna_swapped.to_string()),
+    ("amount", amount.to_string()),
+    ("restaked", restaked.to_string()),
+  ]);
+  if !restaked.is_zero() {
+    let shares = shares_for(restaked, pending.total_delegated, pending.total_shares);
+    let mut pool = LIQUID_STAKING.load(deps.storage)?;
+    pool.shares += shares;
+    LIQUID_STAKING.save(deps.storage, &pool)?;
+    DELEGATION_SHARES.save(deps.storage, &(pending.total_shares + shares))?;
+    response = response.add_messages(delegate_msgs(&state.validators, restaked));
+  }
+  if !sent.is_zero() {
+    response = response.add_message(CosmosMsg::Bank(BankMsg::Send {
+      to_address: state.owner.to_string(),
+      amount: vec![coin(sent.u128(), "uluna")],
+    }));
+  }
+  Ok(response)
+}
+
+pub fn try_start_undelegation(mut deps: DepsMut, env: Env, info: MessageInfo, amount: Uint128) -> Result<Response<TerraMsgWrapper>, ContractError>{
+    let owner = STATE.load(deps.storage)?.owner;
+    if info.sender != owner {
+      return Err(ContractError::Unauthorized {});
+    }
+    if amount.is_zero() {
+      return Err(ContractError::InvalidQuantity {});
+    }
+
+    // the luna staked for receipt holders is theirs to unbond
+    let (total_delegated, total_shares) = delegation_shares(deps.as_ref(), &env)?;
+    let pool_shares = LIQUID_STAKING.may_load(deps.storage)?.unwrap_or_default().shares;
+    let pool_staked = shares_value(pool_shares, total_delegated, total_shares);
+    let (msgs_undelegate, unbonding_ids) = queue_undelegation(deps.branch(), &env, amount, pool_staked, owner)?;
+    let burned = shares_to_burn(amount, total_delegated, total_shares).min(total_shares - pool_shares);
+    DELEGATION_SHARES.save(deps.storage, &(total_shares - burned))?;
+
+    Ok(
+      Response::new().add_attributes(vec![
+        ("method", "try_start_undelegation"),
+        ("amount", &amount.to_string()),
+        ("unbonding_ids", &unbonding_ids.join(",")),
+      ]).add_messages(msgs_undelegate)
+    )
+}
+
+// Undelegates `amount` from every validator in proportion to what it holds, and queues it for
+// `recipient` until the chain returns the luna. `reserved` is delegated luna that cannot be
+// undelegated this way.
+fn queue_undelegation(
+  deps: DepsMut,
+  env: &Env,
+  amount: Uint128,
+  reserved: Uint128,
+  recipient: Addr,
+) -> Result<(Vec<CosmosMsg<TerraMsgWrapper>>, Vec<String>), ContractError> {
+  let delegated: Vec<(String, Uint128)> = deps
+    .querier
+    .query_all_delegations(&env.contract.address)?
+    .into_iter()
+    .map(|delegation| (delegation.validator, delegation.amount.amount))
+    .collect();
+  let total_delegated: Uint128 = delegated.iter().map(|(_, amount)| *amount).sum();
+  let available = total_delegated.saturating_sub(reserved);
+  if amount > available {
+    return Err(ContractError::InsufficientDelegation { amount, delegated: available });
+  }
+
+  let start_time = env.block.time.seconds();
+  let mut unbonding_count = UNBONDING_COUNT.may_load(deps.storage)?.unwrap_or_default();
+  let mut unbonding_ids: Vec<String> = vec![];
+  let mut msgs_undelegate: Vec<CosmosMsg<TerraMsgWrapper>> = vec![];
+  for (validator, amount) in split_by_delegation(amount, &delegated) {
+    unbonding_count += 1;
+    UNBONDING_QUEUE.save(deps.storage, U64Key::new(unbonding_count), &UnbondingEntry {
+      id: unbonding_count,
+      recipient: recipient.clone(),
+      validator: validator.clone(),
+      amount,
+      start_time,
+      completion_time: start_time + UNBONDING_PERIOD,
+      received: None,
+    })?;
+    unbonding_ids.push(unbonding_count.to_string());
+    msgs_undelegate.push(CosmosMsg::Staking(StakingMsg::Undelegate {
+      validator,
+      amount: coin(amount.u128(), "uluna"),
+    }));
+  }
+  UNBONDING_COUNT.save(deps.storage, &unbonding_count)?;
+
+  Ok((msgs_undelegate, unbonding_ids))
+}
+
+pub fn try_claim_unbonded(mut deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response<TerraMsgWrapper>, ContractError> {
+  let (settled, received) = settle_unbonding(deps.branch(), &env)?;
+  let claims: Vec<UnbondingEntry> = settled.into_iter().filter(|entry| entry.recipient == info.sender).collect();
+  if claims.is_empty() {
+    return Err(ContractError::NothingToClaim {});
+  }
+
+  let matured: Uint128 = claims.iter().map(|entry| entry.amount).sum();
+  let claimed: Uint128 = claims.iter().map(|entry| entry.received.unwrap_or_default()).sum();
+  for entry in claims {
+    UNBONDING_QUEUE.remove(deps.storage, U64Key::new(entry.id));
+  }
+  UNBONDING_RECEIVED.save(deps.storage, &(received - claimed))?;
+
+  let mut response = Response::new().add_attributes(vec![
+    ("method", "try_claim_unbonded"),
+    ("matured", &matured.to_string()),
+    ("claimed", &claimed.to_string()),
+  ]);
+  if !claimed.is_zero() {
+    response = response.add_message(CosmosMsg::Bank(BankMsg::Send {
+      to_address: info.sender.to_string(),
+      amount: vec![coin(claimed.u128(), "uluna")],
+    }));
+  }
+  Ok(response)
+}
+
+// Entries are queued in start order with the same unbonding period, so the matured ones come
+// first. The chain returns the luna at the end of the block that reaches the completion time, so
+// an entry only counts as matured from the next block on.
+fn matured_unbonding(deps: Deps, env: &Env) -> StdResult<Vec<UnbondingEntry>> {
+  let now = env.block.time.seconds();
+  let mut matured = vec![];
+  for item in UNBONDING_QUEUE.range(deps.storage, None, None, Order::Ascending) {
+    let (_, entry) = item?;
+    if entry.completion_time >= now {
+      break;
+    }
+    matured.push(entry);
+  }
+  Ok(matured)
+}
+
+// Settles the entries that have matured since the last settlement, and returns every matured
+// entry with the luna now held for unbonding claims. The luna in the balance that isn't already
+// held for earlier entries counts as returned, and when it falls short of what was undelegated,
+// e.g. after a slash, each newly matured entry takes the same cut.
+fn settled_unbonding(deps: Deps, env: &Env) -> StdResult<(Vec<UnbondingEntry>, Uint128)> {
+  let mut matured = matured_unbonding(deps, env)?;
+  let mut received = UNBONDING_RECEIVED.may_load(deps.storage)?.unwrap_or_default();
+  let expected: Uint128 = matured.iter().filter(|entry| entry.received.is_none()).map(|entry| entry.amount).sum();
+  if expected.is_zero() {
+    return Ok((matured, received));
+  }
+
+  let luna_balance = deps.querier.query_balance(&env.contract.address, "uluna")?.amount;
+  let returned = luna_balance.saturating_sub(received).min(expected);
+  for entry in matured.iter_mut().filter(|entry| entry.received.is_none()) {
+    let share = entry.amount.multiply_ratio(returned, expected);
+    entry.received = Some(share);
+    received += share;
+  }
+  Ok((matured, received))
+}
+
+// Saves the result of `settled_unbonding`, so the luna returned stays set aside for its recipients.
+fn settle_unbonding(deps: DepsMut, env: &Env) -> StdResult<(Vec<UnbondingEntry>, Uint128)> {
+  let (settled, received) = settled_unbonding(deps.as_ref(), env)?;
+  for entry in &settled {
+    UNBONDING_QUEUE.save(deps.storage, U64Key::new(entry.id), entry)?;
+  }
+  UNBONDING_RECEIVED.save(deps.storage, &received)?;
+  Ok((settled, received))
+}
+
+pub fn try_deposit(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response<TerraMsgWrapper>, ContractError> {
+  let state = STATE.load(deps.storage)?;
+  let receipt_token = state.receipt_token.ok_or(ContractError::LiquidStakingDisabled {})?;
+
+  if let Some(other) = info.funds.iter().find(|c| c.denom != "uluna") {
+    return Err(ContractError::UnsupportedDenom { denom: other.denom.clone() });
+  }
+  let deposit = info.funds.iter().map(|c| c.amount).sum::<Uint128>();
+
+  // the first deposit sets the rate at one receipt per uluna
+  let mut pool = LIQUID_STAKING.may_load(deps.storage)?.unwrap_or_default();
+  let (total_delegated, total_shares) = delegation_shares(deps.as_ref(), &env)?;
+  let staked = shares_value(pool.shares, total_delegated, total_shares);
+  let receipts = if pool.receipt_supply.is_zero() {
+    deposit
+  } else if staked.is_zero() {
+    Uint128::zero()
+  } else {
+    deposit.multiply_ratio(pool.receipt_supply, staked)
+  };
+  if receipts.is_zero() {
+    return Err(ContractError::InvalidQuantity {});
   }
+  let shares = shares_for(deposit, total_delegated, total_shares);
+  pool.shares += shares;
+  pool.receipt_supply += receipts;
+  LIQUID_STAKING.save(deps.storage, &pool)?;
+  DELEGATION_SHARES.save(deps.storage, &(total_shares + shares))?;
+
+  let msg_mint = CosmosMsg::Wasm(WasmMsg::Execute {
+    contract_addr: receipt_token.to_string(),
+    funds: vec![],
+    msg: to_binary(&Cw20ExecuteMsg::Mint {
+      recipient: info.sender.to_string(),
+      amount: receipts,
+    })?,
+  });
 
   Ok(Response::new()
-    .add_attribute("method", "try_convert_rewards")
-    .add_submessages(submessages))
+    .add_attributes(vec![
+      ("method", "try_deposit"),
+      ("deposit", &deposit.to_string()),
+      ("receipts_minted", &receipts.to_string()),
+    ])
+    .add_messages(delegate_msgs(&state.validators, deposit))
+    .add_message(msg_mint))
 }
 
-pub fn try_send_luna(deps: DepsMut, env: Env, info: MessageInfo, amount: i32) -> Result<Response<TerraMsgWrapper>, ContractError> {
+pub fn try_receive(deps: DepsMut, env: Env, info: MessageInfo, cw20_msg: Cw20ReceiveMsg) -> Result<Response<TerraMsgWrapper>, ContractError> {
+  // only receipts can be redeemed here
+  let receipt_token = STATE.load(deps.storage)?.receipt_token.ok_or(ContractError::LiquidStakingDisabled {})?;
+  if info.sender != receipt_token {
+    return Err(ContractError::Unauthorized {});
+  }
+
+  let holder = deps.api.addr_validate(&cw20_msg.sender)?;
+  match from_binary(&cw20_msg.msg)? {
+    Cw20HookMsg::Unbond {} => try_unbond(deps, env, receipt_token, holder, cw20_msg.amount),
+  }
+}
+
+pub fn try_unbond(
+  deps: DepsMut,
+  env: Env,
+  receipt_token: Addr,
+  holder: Addr,
+  receipts: Uint128,
+) -> Result<Response<TerraMsgWrapper>, ContractError> {
+  let mut pool = LIQUID_STAKING.may_load(deps.storage)?.unwrap_or_default();
+  if receipts.is_zero() || receipts > pool.receipt_supply {
+    return Err(ContractError::InvalidQuantity {});
+  }
+  let (total_delegated, total_shares) = delegation_shares(deps.as_ref(), &env)?;
+  let shares = receipts.multiply_ratio(pool.shares, pool.receipt_supply);
+  let luna = shares_value(shares, total_delegated, total_shares);
+  if luna.is_zero() {
+    return Err(ContractError::InvalidQuantity {});
+  }
+  pool.shares -= shares;
+  pool.receipt_supply -= receipts;
+  LIQUID_STAKING.save(deps.storage, &pool)?;
+  DELEGATION_SHARES.save(deps.storage, &(total_shares - shares))?;
+
+  let (msgs_undelegate, unbonding_ids) = queue_undelegation(deps, &env, luna, Uint128::zero(), holder)?;
+  let msg_burn = CosmosMsg::Wasm(WasmMsg::Execute {
+    contract_addr: receipt_token.to_string(),
+    funds: vec![],
+    msg: to_binary(&Cw20ExecuteMsg::Burn { amount: receipts })?,
+  });
+
+  Ok(Response::new()
+    .add_attributes(vec![
+      ("method", "try_unbond"),
+      ("receipts_burned", &receipts.to_string()),
+      ("amount", &luna.to_string()),
+      ("unbonding_ids", &unbonding_ids.join(",")),
+    ])
+    .add_message(msg_burn)
+    .add_messages(msgs_undelegate))
+}
+
+pub fn try_update_validators(deps: DepsMut, info: MessageInfo, validators: Vec<ValidatorWeight>) -> Result<Response<TerraMsgWrapper>, ContractError> {
+  let mut state = STATE.load(deps.storage)?;
+  if info.sender != state.owner {
+    return Err(ContractError::Unauthorized {});
+  }
+  validate_validators(deps.as_ref(), &validators)?;
+
+  // existing delegations stay where they are, only new ones follow the new weights
+  state.validators = validators;
+  STATE.save(deps.storage, &state)?;
+
+  let validators: Vec<String> = state.validators.iter().map(|v| format!("{}:{}", v.address, v.weight)).collect();
+  Ok(Response::new().add_attributes(vec![
+    ("method", "try_update_validators"),
+    ("validators", &validators.join(",")),
+  ]))
+}
+
+pub fn try_redelegate(
+  deps: DepsMut,
+  env: Env,
+  info: MessageInfo,
+  src_validator: String,
+  dst_validator: String,
+  amount: Option<Uint128>,
+) -> Result<Response<TerraMsgWrapper>, ContractError> {
   let owner = STATE.load(deps.storage)?.owner;
-  if info.sender != owner { 
-    return Err(ContractError::Unauthorized {})
+  if info.sender != owner {
+    return Err(ContractError::Unauthorized {});
+  }
+  if src_validator == dst_validator {
+    return Err(ContractError::SameValidator {});
+  }
+  if deps.querier.query_validator(&dst_validator)?.is_none() {
+    return Err(ContractError::UnknownValidator { validator: dst_validator });
+  }
+
+  let delegation = deps
+    .querier
+    .query_delegation(&env.contract.address, &src_validator)?
+    .ok_or_else(|| ContractError::NoDelegation { validator: src_validator.clone() })?;
+  let amount = amount.unwrap_or(delegation.amount.amount);
+  if amount.is_zero() {
+    return Err(ContractError::InvalidQuantity {});
+  }
+  if amount > delegation.amount.amount {
+    return Err(ContractError::InsufficientDelegation { amount, delegated: delegation.amount.amount });
+  }
+  // stake that was itself redelegated recently cannot move again until that redelegation matures
+  if amount > delegation.can_redelegate.amount {
+    return Err(ContractError::RedelegationUnavailable { amount, available: delegation.can_redelegate.amount });
   }
 
-  let luna_balance = deps.querier.query_balance(env.contract.address, String::from("uluna"))?;
-  if luna_balance.amount.u128() < amount as u128 {
-    return Err(ContractError::InvalidQuantity{});
+  Ok(Response::new()
+    .add_attributes(vec![
+      ("method", "try_redelegate"),
+      ("src_validator", &src_validator),
+      ("dst_validator", &dst_validator),
+      ("amount", &amount.to_string()),
+    ])
+    .add_message(CosmosMsg::Staking(StakingMsg::Redelegate {
+      src_validator,
+      dst_validator,
+      amount: coin(amount.u128(), "uluna"),
+    })))
+}
+
+pub fn try_rebalance(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response<TerraMsgWrapper>, ContractError> {
+  let state = STATE.load(deps.storage)?;
+  if info.sender != state.owner {
+    return Err(ContractError::Unauthorized {});
   }
 
-  let msg = BankMsg::Send {
-    to_address: owner.to_string(),
-    amount: vec![coin(amount as u128, String::from("uluna"))],
+  let delegations = deps.querier.query_all_delegations(&env.contract.address)?;
+  let total_delegated: Uint128 = delegations.iter().map(|d| d.amount.amount).sum();
+  let weights: Vec<(String, Uint128)> = state
+    .validators
+    .iter()
+    .map(|v| (v.address.clone(), Uint128::from(v.weight)))
+    .collect();
+  let targets = split_by_weight(total_delegated, &weights);
+  let target_of = |validator: &str| -> Uint128 {
+    targets.iter().find(|(address, _)| address == validator).map(|(_, target)| *target).unwrap_or_default()
   };
 
+  // validators holding more than their share, by how much can move now
+  let mut surpluses: Vec<(String, Uint128)> = vec![];
+  for delegation in &delegations {
+    let surplus = delegation.amount.amount.saturating_sub(target_of(&delegation.validator));
+    if surplus.is_zero() {
+      continue;
+    }
+    let can_redelegate = deps
+      .querier
+      .query_delegation(&env.contract.address, &delegation.validator)?
+      .map(|full| full.can_redelegate.amount)
+      .unwrap_or_default();
+    let surplus = surplus.min(can_redelegate);
+    if !surplus.is_zero() {
+      surpluses.push((delegation.validator.clone(), surplus));
+    }
+  }
+  // validators holding less than their share, by how much they are short
+  let mut deficits: Vec<(String, Uint128)> = targets
+    .iter()
+    .filter_map(|(validator, target)| {
+      let delegated = delegations
+        .iter()
+        .find(|d| &d.validator == validator)
+        .map(|d| d.amount.amount)
+        .unwrap_or_default();
+      let deficit = target.saturating_sub(delegated);
+      (!deficit.is_zero()).then(|| (validator.clone(), deficit))
+    })
+    .collect();
+
+  // move stake from the first surplus to the first deficit until either side runs out
+  let mut msgs: Vec<CosmosMsg<TerraMsgWrapper>> = vec![];
+  let mut redelegated = Uint128::zero();
+  let (mut i, mut j) = (0, 0);
+  while i < surpluses.len() && j < deficits.len() {
+    let amount = surpluses[i].1.min(deficits[j].1);
+    msgs.push(CosmosMsg::Staking(StakingMsg::Redelegate {
+      src_validator: surpluses[i].0.clone(),
+      dst_validator: deficits[j].0.clone(),
+      amount: coin(amount.u128(), "uluna"),
+    }));
+    redelegated += amount;
+    surpluses[i].1 -= amount;
+    deficits[j].1 -= amount;
+    if surpluses[i].1.is_zero() {
+      i += 1;
+    }
+    if deficits[j].1.is_zero() {
+      j += 1;
+    }
+  }
+
   Ok(Response::new()
     .add_attributes(vec![
-      ("method", "try_send_luna"),
-      ("initial_luna_balance", &luna_balance.to_string()),
-      ("amount", &amount.to_string())
+      ("method", "try_rebalance"),
+      ("redelegated", &redelegated.to_string()),
     ])
-    .add_message(CosmosMsg::Bank(msg)))
+    .add_messages(msgs))
 }
 
-pub fn try_start_undelegation(deps: DepsMut, env: Env, info: MessageInfo, amount: u128) -> Result<Response<TerraMsgWrapper>, ContractError>{
-    //read params
-    let coin_denom = "uluna";
-    let mut messages: Vec<CosmosMsg> = vec![];
-    let msg_undelegate: CosmosMsg<TerraMsgWrapper> = CosmosMsg::Staking(StakingMsg::Undelegate {
-      validator: String::from(VALIDATOR),
-      amount: coin(amount, coin_denom),
-    });
+fn validate_validators(deps: Deps, validators: &[ValidatorWeight]) -> Result<(), ContractError> {
+  if validators.is_empty() {
+    return Err(ContractError::InvalidValidatorSet {});
+  }
+  for (i, validator) in validators.iter().enumerate() {
+    if validator.weight == 0 || validators[..i].iter().any(|v| v.address == validator.address) {
+      return Err(ContractError::InvalidValidatorSet {});
+    }
+    if deps.querier.query_validator(&validator.address)?.is_none() {
+      return Err(ContractError::UnknownValidator { validator: validator.address.clone() });
+    }
+  }
+  Ok(())
+}
 
-    Ok(
-      Response::new().add_attributes(vec![
-        ("method", "try_start_undelegation"),
-        ("amount", &amount.to_string())
-      ]).add_messages(vec![msg_undelegate])
-    )
+// Delegates `amount` across the validator set by weight.
+fn delegate_msgs(validators: &[ValidatorWeight], amount: Uint128) -> Vec<CosmosMsg<TerraMsgWrapper>> {
+  let weights: Vec<(String, Uint128)> = validators
+    .iter()
+    .map(|v| (v.address.clone(), Uint128::from(v.weight)))
+    .collect();
+  split_by_weight(amount, &weights)
+    .into_iter()
+    .map(|(validator, amount)| CosmosMsg::Staking(StakingMsg::Delegate {
+      validator,
+      amount: Coin {
+          denom: String::from("uluna"),
+          amount,
+      },
+    }))
+    .collect()
+}
+
+// What the contract has delegated in total, and the shares that is split into. Shares follow the
+// delegations' actual value, so slashes and restaked rewards show up in what every share is worth.
+fn delegation_shares(deps: Deps, env: &Env) -> StdResult<(Uint128, Uint128)> {
+  let total_delegated = deps
+    .querier
+    .query_all_delegations(&env.contract.address)?
+    .iter()
+    .map(|delegation| delegation.amount.amount)
+    .sum();
+  let total_shares = DELEGATION_SHARES.may_load(deps.storage)?.unwrap_or_default();
+  Ok((total_delegated, total_shares))
+}
+
+// Shares for delegating `amount` more. The first delegation sets a share at one uluna.
+fn shares_for(amount: Uint128, total_delegated: Uint128, total_shares: Uint128) -> Uint128 {
+  if total_shares.is_zero() || total_delegated.is_zero() {
+    amount
+  } else {
+    amount.multiply_ratio(total_shares, total_delegated)
+  }
+}
+
+// The uluna `shares` are worth, rounded down.
+fn shares_value(shares: Uint128, total_delegated: Uint128, total_shares: Uint128) -> Uint128 {
+  if total_shares.is_zero() {
+    Uint128::zero()
+  } else {
+    shares.multiply_ratio(total_delegated, total_shares)
+  }
+}
+
+// Shares given up by undelegating `amount`, rounded up so the other shares never lose value.
+fn shares_to_burn(amount: Uint128, total_delegated: Uint128, total_shares: Uint128) -> Uint128 {
+  if total_delegated.is_zero() {
+    return Uint128::zero();
+  }
+  let shares = amount.multiply_ratio(total_shares, total_delegated);
+  if shares.full_mul(total_delegated) < amount.full_mul(total_shares) {
+    shares + Uint128::new(1)
+  } else {
+    shares
+  }
+}
+
+// Splits `amount` in proportion to the weights, rounding down. The remainder goes to the last
+// entry, and entries whose share is zero are left out.
+fn split_by_weight(amount: Uint128, weights: &[(String, Uint128)]) -> Vec<(String, Uint128)> {
+  let total_weight: Uint128 = weights.iter().map(|(_, weight)| *weight).sum();
+  if total_weight.is_zero() {
+    return vec![];
+  }
+
+  let mut remaining = amount;
+  let mut shares: Vec<(String, Uint128)> = weights
+    .iter()
+    .map(|(address, weight)| {
+      let share = amount.multiply_ratio(*weight, total_weight);
+      remaining -= share;
+      (address.clone(), share)
+    })
+    .collect();
+  if let Some(last) = shares.last_mut() {
+    last.1 += remaining;
+  }
+  shares.retain(|(_, share)| !share.is_zero());
+  shares
+}
+
+// Splits `amount` in proportion to what each validator holds, rounding down. The remainder goes
+// to the validators with room left, largest delegation first, so no validator is asked for more
+// than was delegated to it. `amount` must not exceed the total delegated.
+fn split_by_delegation(amount: Uint128, delegated: &[(String, Uint128)]) -> Vec<(String, Uint128)> {
+  let total_delegated: Uint128 = delegated.iter().map(|(_, amount)| *amount).sum();
+  if total_delegated.is_zero() {
+    return vec![];
+  }
+
+  let mut remaining = amount;
+  let mut shares: Vec<(String, Uint128)> = delegated
+    .iter()
+    .map(|(validator, delegation)| {
+      let share = amount.multiply_ratio(*delegation, total_delegated);
+      remaining -= share;
+      (validator.clone(), share)
+    })
+    .collect();
+
+  let mut by_size: Vec<usize> = (0..delegated.len()).collect();
+  by_size.sort_by(|a, b| delegated[*b].1.cmp(&delegated[*a].1));
+  for i in by_size {
+    if remaining.is_zero() {
+      break;
+    }
+    let extra = remaining.min(delegated[i].1 - shares[i].1);
+    shares[i].1 += extra;
+    remaining -= extra;
+  }
+  shares.retain(|(_, share)| !share.is_zero());
+  shares
 }
 
 fn get_price(deps: Deps) -> Result<PriceResponse, ContractError> {
-  let oracle_address = STATE.load(deps.storage)?.oracle_address;
+  let state = STATE.load(deps.storage)?;
+  // the oracle quotes Lemons in uluna
+  let pair = Pair::new(AssetInfo::cw20(state.token_address), AssetInfo::native("uluna"));
   let price_response: PriceResponse = deps.querier.query_wasm_smart(
-      oracle_address,
-      &QueryMsg::QueryPrice {},
+      state.oracle_address,
+      &OracleQueryMsg::QueryPrice { pair },
   )?;
   Ok(price_response)
 }
 
+// Loads the oracle price and rejects it when it is older than the configured max age.
+fn get_fresh_price(deps: Deps, env: &Env) -> Result<PriceResponse, ContractError> {
+  let price_response = get_price(deps)?;
+  if price_response.status == PriceStatus::Paused {
+    return Err(ContractError::PriceFeedPaused {});
+  }
+  if let Some(max_age) = STATE.load(deps.storage)?.max_price_age {
+    let age = env.block.time.seconds().saturating_sub(price_response.last_updated_time);
+    if age > max_age {
+      return Err(ContractError::StalePrice { age, max_age });
+    }
+  }
+  Ok(price_response)
+}
+
 fn get_balance_of_cw20(deps: Deps, address: Addr) -> Result<BalanceResponse, ContractError> {
   let token_address = STATE.load(deps.storage)?.token_address;
   let balance_response: BalanceResponse = deps.querier.query_wasm_smart(
       token_address,
-      &QueryMsg::Balance { address: address }
+      &QueryMsg::Balance { address }
   )?;
   Ok(balance_response)
 }
@@ -243,24 +911,97 @@ pub fn migrate(_deps: DepsMut, _env: Env, _msg: Empty) -> StdResult<Response> {
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
   match msg {
     QueryMsg::QueryTokenAddress {} => {
-      let token_address_response = QueryTokenAddressResponse { 
+      let token_address_response = QueryTokenAddressResponse {
         token_address: STATE.load(deps.storage)?.token_address
       };
-      return to_binary(&token_address_response);
+      to_binary(&token_address_response)
     },
-    QueryMsg::QueryPrice {} => { 
-      let price_response: PriceResponse = get_price(deps).unwrap();
-      return to_binary(&price_response)
+    QueryMsg::QueryPrice {} => {
+      let price_response = get_price(deps).map_err(|err| StdError::generic_err(err.to_string()))?;
+      to_binary(&price_response)
     },
     QueryMsg::Balance { address } => {
-      return to_binary(&{ address })
-    }
+      to_binary(&{ address })
+    },
+    QueryMsg::Delegations {} => to_binary(&query_delegations(deps, env)?),
+    QueryMsg::UnbondingQueue { start_after, limit } => to_binary(&query_unbonding_queue(deps, start_after, limit)?),
+    QueryMsg::Unbonding { address } => to_binary(&query_unbonding(deps, env, address)?),
+    QueryMsg::LiquidStaking {} => to_binary(&query_liquid_staking(deps, env)?),
   }
 }
 
+fn query_delegations(deps: Deps, env: Env) -> StdResult<DelegationsResponse> {
+  let state = STATE.load(deps.storage)?;
+  let mut delegated = deps.querier.query_all_delegations(&env.contract.address)?;
+
+  let mut delegations: Vec<ValidatorDelegation> = vec![];
+  for validator in state.validators {
+    let amount = match delegated.iter().position(|d| d.validator == validator.address) {
+      Some(i) => delegated.remove(i).amount.amount,
+      None => Uint128::zero(),
+    };
+    delegations.push(ValidatorDelegation { validator: validator.address, weight: validator.weight, delegated: amount });
+  }
+  // validators that were removed from the set but still hold a delegation
+  delegations.extend(delegated.into_iter().map(|d| ValidatorDelegation {
+    validator: d.validator,
+    weight: 0,
+    delegated: d.amount.amount,
+  }));
+
+  let total_delegated = delegations.iter().map(|d| d.delegated).sum();
+  Ok(DelegationsResponse { delegations, total_delegated })
+}
+
+fn query_unbonding_queue(deps: Deps, start_after: Option<u64>, limit: Option<u32>) -> StdResult<UnbondingQueueResponse> {
+  let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+  let start = start_after.map(Bound::exclusive_int);
+
+  let entries = UNBONDING_QUEUE
+    .range(deps.storage, start, None, Order::Ascending)
+    .take(limit)
+    .map(|item| item.map(|(_, entry)| entry))
+    .collect::<StdResult<Vec<_>>>()?;
+  Ok(UnbondingQueueResponse { entries })
+}
+
+fn query_unbonding(deps: Deps, env: Env, address: Option<String>) -> StdResult<UnbondingResponse> {
+  let recipient = address.map(|address| deps.api.addr_validate(&address)).transpose()?;
+  let total_unbonding = UNBONDING_QUEUE
+    .range(deps.storage, None, None, Order::Ascending)
+    .filter(|item| match (item, &recipient) {
+      (Ok((_, entry)), Some(recipient)) => entry.recipient == *recipient,
+      _ => true,
+    })
+    .map(|item| item.map(|(_, entry)| entry.amount))
+    .sum::<StdResult<Uint128>>()?;
+  let (settled, _) = settled_unbonding(deps, &env)?;
+  let settled: Vec<UnbondingEntry> = settled
+    .into_iter()
+    .filter(|entry| recipient.as_ref().map_or(true, |recipient| entry.recipient == *recipient))
+    .collect();
+  let matured = settled.iter().map(|entry| entry.amount).sum();
+  let claimable = settled.iter().map(|entry| entry.received.unwrap_or_default()).sum();
+
+  Ok(UnbondingResponse { total_unbonding, matured, claimable })
+}
+
+fn query_liquid_staking(deps: Deps, env: Env) -> StdResult<LiquidStakingResponse> {
+  let receipt_token = STATE.load(deps.storage)?.receipt_token;
+  let pool = LIQUID_STAKING.may_load(deps.storage)?.unwrap_or_default();
+  let (total_delegated, total_shares) = delegation_shares(deps, &env)?;
+  let staked = shares_value(pool.shares, total_delegated, total_shares);
+  let exchange_rate = if pool.receipt_supply.is_zero() {
+    Decimal::one()
+  } else {
+    Decimal::from_ratio(staked, pool.receipt_supply)
+  };
+  Ok(LiquidStakingResponse { receipt_token, staked, receipt_supply: pool.receipt_supply, exchange_rate })
+}
+
 pub fn query_exchange_rates(
     deps: &DepsMut,
     base_denom: String,
@@ -275,17 +1016,35 @@ pub fn query_exchange_rates(
 mod tests {
     use super::*;
     use cosmwasm_std::testing::{mock_env, mock_info, MOCK_CONTRACT_ADDR};
-    use cosmwasm_std::{coins, from_binary, Addr};
+    use cosmwasm_std::{coins, from_binary, Addr, ContractResult, SubMsgExecutionResponse};
     use testing::mock_querier::{mock_dependencies};
 
     const TOKEN: &str = "hyp0000";
     const ORACLE: &str = "oracle000";
+    const VALIDATOR: &str = "terravaloper1vk20anceu6h9s00d27pjlvslz3avetkvnwmr35";
+    const VALIDATOR2: &str = "terravaloper1krj7amhhagjnyg2tkkuh6l0550y733jnjnnlzy";
+    const RECEIPT: &str = "receipt000";
+
+    fn validator(address: &str, weight: u64) -> ValidatorWeight {
+      ValidatorWeight { address: String::from(address), weight }
+    }
+
+    fn default_instantiate_msg() -> InstantiateMsg {
+      InstantiateMsg {
+        token_address: Addr::unchecked(TOKEN),
+        oracle_address: Addr::unchecked(ORACLE),
+        max_price_age: None,
+        validators: vec![validator(VALIDATOR, 1)],
+        receipt_token: None,
+      }
+    }
 
     #[test]
     fn proper_initialization() {
       let mut deps = mock_dependencies(&coins(2, "token"));
+      deps.querier.with_delegations(&[VALIDATOR], &[]);
 
-      let msg = InstantiateMsg { token_address: Addr::unchecked(TOKEN), oracle_address: Addr::unchecked(ORACLE) };
+      let msg = default_instantiate_msg();
       let info = mock_info("creator", &coins(1000, "earth"));
       let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
     }
@@ -293,42 +1052,712 @@ mod tests {
     #[test]
     fn try_query() {
       let mut deps = mock_dependencies(&coins(2, "token"));
-      deps.querier.with_oracle_price(15);
+      deps.querier.with_delegations(&[VALIDATOR], &[]);
+      deps.querier.with_oracle_price(Decimal::percent(1500));
 
-      let msg = InstantiateMsg { token_address: Addr::unchecked(TOKEN), oracle_address: Addr::unchecked("oracle000") };
+      let msg = default_instantiate_msg();
       let info = mock_info("creator", &coins(1_000_000, "uluna"));
       let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
 
       // it worked, let's query the state
       let res = query(deps.as_ref(), mock_env(), QueryMsg::QueryPrice {}).unwrap();
       let price_response: PriceResponse = from_binary(&res).unwrap();
-      assert_eq!(15, price_response.price);
+      assert_eq!(Decimal::percent(1500), price_response.price);
+    }
+
+    #[test]
+    fn try_query_unreachable_oracle() {
+      let mut deps = mock_dependencies(&[]);
+      deps.querier.with_delegations(&[VALIDATOR], &[]);
+
+      let msg = InstantiateMsg { oracle_address: Addr::unchecked("nowhere"), ..default_instantiate_msg() };
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      assert!(query(deps.as_ref(), mock_env(), QueryMsg::QueryPrice {}).is_err());
     }
 
     #[test]
     fn try_buy() {
       let mut deps = mock_dependencies(&coins(1000, TOKEN));
-      deps.querier.with_oracle_price(10);
+      deps.querier.with_delegations(&[VALIDATOR], &[]);
+      deps.querier.with_oracle_price(Decimal::percent(1000));
       deps.querier.with_token_balances(&[(
         &TOKEN.to_string(),
         &[(
             &MOCK_CONTRACT_ADDR.to_string(),
-            &Uint128::new(1_000_000_000_000 as u128),
+            &Uint128::new(1_000_000_000_000),
         )],
       )]);
 
-      let msg = InstantiateMsg { token_address: Addr::unchecked(TOKEN), oracle_address: Addr::unchecked("oracle000") };
+      let msg = default_instantiate_msg();
       let info = mock_info("creator", &coins(1_000_000, "uluna"));
       let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-      let msg = ExecuteMsg::Buy {};
+      let msg = ExecuteMsg::Buy { min_tokens_out: None, max_price: None, deadline: None };
       let info = mock_info("buyer", &coins(1_000, String::from("uluna")));
       let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
       assert_eq!("100", res.attributes[2].value);
+
+      // only the uluna paid for whole tokens is delegated, the rest goes back to the buyer
+      deps.querier.with_oracle_price(Decimal::percent(300));
+      let info = mock_info("buyer", &coins(1_000, "uluna"));
+      let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Buy { min_tokens_out: None, max_price: None, deadline: None }).unwrap();
+      assert_eq!("333", res.attributes[2].value);
+      assert_eq!(
+        CosmosMsg::Staking(StakingMsg::Delegate { validator: String::from(VALIDATOR), amount: coin(999, "uluna") }),
+        res.messages[1].msg
+      );
+      assert_eq!(
+        CosmosMsg::Bank(BankMsg::Send { to_address: String::from("buyer"), amount: coins(1, "uluna") }),
+        res.messages[2].msg
+      );
+    }
+
+    #[test]
+    fn try_buy_enforces_slippage_limits() {
+      let mut deps = mock_dependencies(&[]);
+      deps.querier.with_delegations(&[VALIDATOR], &[]);
+      deps.querier.with_oracle_price(Decimal::percent(1000));
+      deps.querier.with_token_balances(&[(
+        &TOKEN.to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::new(1_000_000))],
+      )]);
+
+      let msg = default_instantiate_msg();
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      let now = mock_env().block.time.seconds();
+      let msg = ExecuteMsg::Buy { min_tokens_out: None, max_price: None, deadline: Some(now - 1) };
+      let err = execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(1_000, "uluna")), msg).unwrap_err();
+      assert!(matches!(err, ContractError::DeadlineExceeded { .. }));
+
+      let msg = ExecuteMsg::Buy { min_tokens_out: None, max_price: Some(Decimal::percent(900)), deadline: None };
+      let err = execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(1_000, "uluna")), msg).unwrap_err();
+      assert!(matches!(err, ContractError::MaxPriceExceeded { .. }));
+
+      let msg = ExecuteMsg::Buy { min_tokens_out: Some(Uint128::new(101)), max_price: None, deadline: None };
+      let err = execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(1_000, "uluna")), msg).unwrap_err();
+      assert!(matches!(err, ContractError::MinTokensOutNotMet { .. }));
+
+      // limits that are met let the purchase through
+      let msg = ExecuteMsg::Buy {
+        min_tokens_out: Some(Uint128::new(100)),
+        max_price: Some(Decimal::percent(1000)),
+        deadline: Some(now),
+      };
+      let _res = execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(1_000, "uluna")), msg).unwrap();
+    }
+
+    #[test]
+    fn try_buy_rejects_stale_price() {
+      let mut deps = mock_dependencies(&[]);
+      deps.querier.with_delegations(&[VALIDATOR], &[]);
+      let env = mock_env();
+      deps.querier.with_oracle_price_updated_at(Decimal::percent(1000), env.block.height - 100, env.block.time.seconds() - 600);
+      deps.querier.with_token_balances(&[(
+        &TOKEN.to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::new(1_000_000))],
+      )]);
+
+      let msg = InstantiateMsg { max_price_age: Some(300), ..default_instantiate_msg() };
+      let _res = instantiate(deps.as_mut(), env.clone(), mock_info("creator", &[]), msg).unwrap();
+
+      let info = mock_info("buyer", &coins(1_000, "uluna"));
+      let err = execute(deps.as_mut(), env, info, ExecuteMsg::Buy { min_tokens_out: None, max_price: None, deadline: None }).unwrap_err();
+      assert!(matches!(err, ContractError::StalePrice { age: 600, max_age: 300 }));
+    }
+
+    #[test]
+    fn try_buy_rejects_paused_price() {
+      let mut deps = mock_dependencies(&[]);
+      deps.querier.with_delegations(&[VALIDATOR], &[]);
+      deps.querier.with_oracle_price(Decimal::percent(1000));
+      deps.querier.with_oracle_status(PriceStatus::Paused);
+
+      let msg = default_instantiate_msg();
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      let info = mock_info("buyer", &coins(1_000, "uluna"));
+      let err = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Buy { min_tokens_out: None, max_price: None, deadline: None }).unwrap_err();
+      assert!(matches!(err, ContractError::PriceFeedPaused {}));
+    }
+
+    #[test]
+    fn try_buy_rejects_other_denoms() {
+      let mut deps = mock_dependencies(&[]);
+      deps.querier.with_delegations(&[VALIDATOR], &[]);
+      deps.querier.with_oracle_price(Decimal::percent(1000));
+
+      let msg = default_instantiate_msg();
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      let info = mock_info("buyer", &[coin(1_000, "uluna"), coin(1_000, "uusd")]);
+      let err = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Buy { min_tokens_out: None, max_price: None, deadline: None }).unwrap_err();
+      assert!(matches!(err, ContractError::UnsupportedDenom { denom } if denom == "uusd"));
+    }
+
+    #[test]
+    fn try_buy_splits_delegation_by_weight() {
+      let mut deps = mock_dependencies(&[]);
+      deps.querier.with_delegations(&[VALIDATOR, VALIDATOR2], &[]);
+      deps.querier.with_oracle_price(Decimal::percent(100));
+      deps.querier.with_token_balances(&[(
+        &TOKEN.to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::new(1_000_000))],
+      )]);
+
+      let msg = InstantiateMsg {
+        validators: vec![validator(VALIDATOR, 1), validator(VALIDATOR2, 2)],
+        ..default_instantiate_msg()
+      };
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      // the rounding remainder goes to the last validator
+      let info = mock_info("buyer", &coins(1_000, "uluna"));
+      let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Buy { min_tokens_out: None, max_price: None, deadline: None }).unwrap();
+      assert_eq!(3, res.messages.len());
+      assert_eq!(
+        CosmosMsg::Staking(StakingMsg::Delegate { validator: String::from(VALIDATOR), amount: coin(333, "uluna") }),
+        res.messages[1].msg
+      );
+      assert_eq!(
+        CosmosMsg::Staking(StakingMsg::Delegate { validator: String::from(VALIDATOR2), amount: coin(667, "uluna") }),
+        res.messages[2].msg
+      );
+
+      // shares that round down to nothing are not delegated
+      let info = mock_info("buyer", &coins(1, "uluna"));
+      let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Buy { min_tokens_out: None, max_price: None, deadline: None }).unwrap();
+      assert_eq!(2, res.messages.len());
+      assert_eq!(
+        CosmosMsg::Staking(StakingMsg::Delegate { validator: String::from(VALIDATOR2), amount: coin(1, "uluna") }),
+        res.messages[1].msg
+      );
+    }
+
+    #[test]
+    fn update_validators() {
+      let mut deps = mock_dependencies(&[]);
+      deps.querier.with_delegations(&[VALIDATOR, VALIDATOR2], &[]);
+
+      let mut msg = InstantiateMsg { validators: vec![], ..default_instantiate_msg() };
+      let err = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg.clone()).unwrap_err();
+      assert!(matches!(err, ContractError::InvalidValidatorSet {}));
+      msg.validators = vec![validator(VALIDATOR, 1)];
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      let msg = ExecuteMsg::UpdateValidators { validators: vec![validator(VALIDATOR2, 1)] };
+      let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), msg).unwrap_err();
+      assert!(matches!(err, ContractError::Unauthorized {}));
+
+      let invalid_sets = vec![
+        vec![validator(VALIDATOR, 0)],
+        vec![validator(VALIDATOR, 1), validator(VALIDATOR, 2)],
+      ];
+      for validators in invalid_sets {
+        let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), ExecuteMsg::UpdateValidators { validators }).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidValidatorSet {}));
+      }
+      let msg = ExecuteMsg::UpdateValidators { validators: vec![validator("terravaloper1unknown", 1)] };
+      let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap_err();
+      assert!(matches!(err, ContractError::UnknownValidator { validator } if validator == "terravaloper1unknown"));
+
+      let msg = ExecuteMsg::UpdateValidators { validators: vec![validator(VALIDATOR2, 3), validator(VALIDATOR, 1)] };
+      let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+      assert_eq!(
+        vec![validator(VALIDATOR2, 3), validator(VALIDATOR, 1)],
+        STATE.load(&deps.storage).unwrap().validators
+      );
+    }
+
+    #[test]
+    fn query_delegations() {
+      let mut deps = mock_dependencies(&[]);
+      let removed = "terravaloper1removed";
+      deps.querier.with_delegations(&[VALIDATOR, VALIDATOR2, removed], &[(VALIDATOR, 600), (removed, 50)]);
+
+      let msg = InstantiateMsg {
+        validators: vec![validator(VALIDATOR, 2), validator(VALIDATOR2, 1)],
+        ..default_instantiate_msg()
+      };
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      let res: DelegationsResponse = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Delegations {}).unwrap()).unwrap();
+      assert_eq!(
+        vec![
+          ValidatorDelegation { validator: String::from(VALIDATOR), weight: 2, delegated: Uint128::new(600) },
+          ValidatorDelegation { validator: String::from(VALIDATOR2), weight: 1, delegated: Uint128::zero() },
+          ValidatorDelegation { validator: String::from(removed), weight: 0, delegated: Uint128::new(50) },
+        ],
+        res.delegations
+      );
+      assert_eq!(Uint128::new(650), res.total_delegated);
+    }
+
+    #[test]
+    fn try_start_undelegation_follows_delegations() {
+      let mut deps = mock_dependencies(&[]);
+      deps.querier.with_delegations(&[VALIDATOR, VALIDATOR2], &[(VALIDATOR, 300), (VALIDATOR2, 100)]);
+
+      let msg = InstantiateMsg { validators: vec![validator(VALIDATOR2, 1)], ..default_instantiate_msg() };
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      let msg = ExecuteMsg::StartUndelegation { amount: Uint128::new(200) };
+      let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+      assert_eq!(
+        vec![
+          CosmosMsg::Staking(StakingMsg::Undelegate { validator: String::from(VALIDATOR), amount: coin(150, "uluna") }),
+          CosmosMsg::Staking(StakingMsg::Undelegate { validator: String::from(VALIDATOR2), amount: coin(50, "uluna") }),
+        ],
+        res.messages.into_iter().map(|m| m.msg).collect::<Vec<_>>()
+      );
+
+      let msg = ExecuteMsg::StartUndelegation { amount: Uint128::new(401) };
+      let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap_err();
+      assert!(matches!(err, ContractError::InsufficientDelegation { .. }));
+
+      let msg = ExecuteMsg::StartUndelegation { amount: Uint128::new(100) };
+      let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), msg).unwrap_err();
+      assert!(matches!(err, ContractError::Unauthorized {}));
+    }
+
+    #[test]
+    fn undelegation_remainder_stays_within_delegations() {
+      let delegated = vec![
+        (String::from("a"), Uint128::new(5)),
+        (String::from("b"), Uint128::new(5)),
+        (String::from("c"), Uint128::new(1)),
+      ];
+      // shares of 4, 4 and 0 leave 2 over, which only the larger delegations have room for
+      assert_eq!(
+        vec![(String::from("a"), Uint128::new(5)), (String::from("b"), Uint128::new(5))],
+        split_by_delegation(Uint128::new(10), &delegated)
+      );
+      assert_eq!(
+        vec![
+          (String::from("a"), Uint128::new(5)),
+          (String::from("b"), Uint128::new(5)),
+          (String::from("c"), Uint128::new(1)),
+        ],
+        split_by_delegation(Uint128::new(11), &delegated)
+      );
+    }
+
+    #[test]
+    fn try_claim_unbonded() {
+      let mut deps = mock_dependencies(&[]);
+      deps.querier.with_delegations(&[VALIDATOR, VALIDATOR2], &[(VALIDATOR, 300), (VALIDATOR2, 100)]);
+
+      let msg = default_instantiate_msg();
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      let start = mock_env().block.time.seconds();
+      let msg = ExecuteMsg::StartUndelegation { amount: Uint128::new(200) };
+      let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+      assert_eq!("1,2", res.attributes[2].value);
+      let mut later = mock_env();
+      later.block.time = later.block.time.plus_seconds(86_400);
+      let msg = ExecuteMsg::StartUndelegation { amount: Uint128::new(40) };
+      let _res = execute(deps.as_mut(), later, mock_info("creator", &[]), msg).unwrap();
+
+      let res: UnbondingQueueResponse = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::UnbondingQueue { start_after: None, limit: None }).unwrap()).unwrap();
+      assert_eq!(4, res.entries.len());
+      assert_eq!(
+        UnbondingEntry {
+          id: 1,
+          recipient: Addr::unchecked("creator"),
+          validator: String::from(VALIDATOR),
+          amount: Uint128::new(150),
+          start_time: start,
+          completion_time: start + UNBONDING_PERIOD,
+          received: None,
+        },
+        res.entries[0]
+      );
+      let res: UnbondingQueueResponse = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::UnbondingQueue { start_after: Some(2), limit: Some(1) }).unwrap()).unwrap();
+      assert_eq!(vec![3], res.entries.iter().map(|e| e.id).collect::<Vec<_>>());
+
+      // nothing can be claimed before the unbonding period is over
+      let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), ExecuteMsg::ClaimUnbonded {}).unwrap_err();
+      assert!(matches!(err, ContractError::NothingToClaim {}));
+
+      // the luna only comes back at the end of the block that completes the unbonding
+      let mut env = mock_env();
+      env.block.time = env.block.time.plus_seconds(UNBONDING_PERIOD);
+      deps.querier.with_balance(&coins(180, "uluna"));
+      let err = execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::ClaimUnbonded {}).unwrap_err();
+      assert!(matches!(err, ContractError::NothingToClaim {}));
+
+      // only the first undelegation has matured, and the chain has returned less than it
+      env.block.time = env.block.time.plus_seconds(1);
+      let res: UnbondingResponse = from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::Unbonding { address: None }).unwrap()).unwrap();
+      assert_eq!(UnbondingResponse { total_unbonding: Uint128::new(240), matured: Uint128::new(200), claimable: Uint128::new(180) }, res);
+
+      let err = execute(deps.as_mut(), env.clone(), mock_info("anyone", &[]), ExecuteMsg::ClaimUnbonded {}).unwrap_err();
+      assert!(matches!(err, ContractError::NothingToClaim {}));
+      let res = execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::ClaimUnbonded {}).unwrap();
+      assert_eq!(
+        vec![SubMsg::new(BankMsg::Send { to_address: String::from("creator"), amount: coins(180, "uluna") })],
+        res.messages
+      );
+
+      // the shortfall was lost to the slash, so the claimed entries are gone
+      let res: UnbondingQueueResponse = from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::UnbondingQueue { start_after: None, limit: None }).unwrap()).unwrap();
+      assert_eq!(vec![(3, Uint128::new(30)), (4, Uint128::new(10))], res.entries.iter().map(|e| (e.id, e.amount)).collect::<Vec<_>>());
+      deps.querier.with_balance(&[]);
+      let err = execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::ClaimUnbonded {}).unwrap_err();
+      assert!(matches!(err, ContractError::NothingToClaim {}));
+    }
+
+    #[test]
+    fn claim_unbonded_splits_shortfall_pro_rata() {
+      let mut deps = mock_dependencies(&[]);
+      deps.querier.with_delegations(&[VALIDATOR], &[]);
+
+      let msg = InstantiateMsg { receipt_token: Some(Addr::unchecked(RECEIPT)), ..default_instantiate_msg() };
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      let _res = execute(deps.as_mut(), mock_env(), mock_info("alice", &coins(600, "uluna")), ExecuteMsg::Deposit {}).unwrap();
+      deps.querier.with_delegations(&[VALIDATOR], &[(VALIDATOR, 600)]);
+      let _res = execute(deps.as_mut(), mock_env(), mock_info("bob", &coins(400, "uluna")), ExecuteMsg::Deposit {}).unwrap();
+      let unbond = |sender: &str, amount: u128| ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: String::from(sender),
+        amount: Uint128::new(amount),
+        msg: to_binary(&Cw20HookMsg::Unbond {}).unwrap(),
+      });
+      deps.querier.with_delegations(&[VALIDATOR], &[(VALIDATOR, 1_000)]);
+      let _res = execute(deps.as_mut(), mock_env(), mock_info(RECEIPT, &[]), unbond("alice", 600)).unwrap();
+      deps.querier.with_delegations(&[VALIDATOR], &[(VALIDATOR, 400)]);
+      let _res = execute(deps.as_mut(), mock_env(), mock_info(RECEIPT, &[]), unbond("bob", 400)).unwrap();
+
+      // the unbonding luna was slashed by 20%, which both holders bear whoever claims first
+      let mut env = mock_env();
+      env.block.time = env.block.time.plus_seconds(UNBONDING_PERIOD + 1);
+      deps.querier.with_balance(&coins(800, "uluna"));
+      let res = execute(deps.as_mut(), env.clone(), mock_info("bob", &[]), ExecuteMsg::ClaimUnbonded {}).unwrap();
+      assert_eq!(
+        vec![SubMsg::new(BankMsg::Send { to_address: String::from("bob"), amount: coins(320, "uluna") })],
+        res.messages
+      );
+
+      // luna arriving later, e.g. rewards, doesn't add to what was returned for alice
+      deps.querier.with_balance(&coins(580, "uluna"));
+      let msg = QueryMsg::Unbonding { address: Some(String::from("alice")) };
+      let res: UnbondingResponse = from_binary(&query(deps.as_ref(), env.clone(), msg).unwrap()).unwrap();
+      assert_eq!(UnbondingResponse { total_unbonding: Uint128::new(600), matured: Uint128::new(600), claimable: Uint128::new(480) }, res);
+      let res = execute(deps.as_mut(), env, mock_info("alice", &[]), ExecuteMsg::ClaimUnbonded {}).unwrap();
+      assert_eq!(
+        vec![SubMsg::new(BankMsg::Send { to_address: String::from("alice"), amount: coins(480, "uluna") })],
+        res.messages
+      );
+      assert_eq!(Uint128::zero(), UNBONDING_RECEIVED.load(&deps.storage).unwrap());
+    }
+
+    #[test]
+    fn try_redelegate() {
+      let mut deps = mock_dependencies(&[]);
+      deps.querier.with_delegations(&[VALIDATOR, VALIDATOR2], &[(VALIDATOR, 500)]);
+
+      let msg = default_instantiate_msg();
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      let redelegate = |src: &str, dst: &str, amount: Option<u128>| ExecuteMsg::Redelegate {
+        src_validator: String::from(src),
+        dst_validator: String::from(dst),
+        amount: amount.map(Uint128::new),
+      };
+
+      let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), redelegate(VALIDATOR, VALIDATOR2, None)).unwrap_err();
+      assert!(matches!(err, ContractError::Unauthorized {}));
+      let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), redelegate(VALIDATOR, VALIDATOR, None)).unwrap_err();
+      assert!(matches!(err, ContractError::SameValidator {}));
+      let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), redelegate(VALIDATOR, "terravaloper1unknown", None)).unwrap_err();
+      assert!(matches!(err, ContractError::UnknownValidator { .. }));
+      let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), redelegate(VALIDATOR2, VALIDATOR, None)).unwrap_err();
+      assert!(matches!(err, ContractError::NoDelegation { validator } if validator == VALIDATOR2));
+      let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), redelegate(VALIDATOR, VALIDATOR2, Some(501))).unwrap_err();
+      assert!(matches!(err, ContractError::InsufficientDelegation { .. }));
+
+      let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), redelegate(VALIDATOR, VALIDATOR2, Some(200))).unwrap();
+      assert_eq!(
+        CosmosMsg::Staking(StakingMsg::Redelegate {
+          src_validator: String::from(VALIDATOR),
+          dst_validator: String::from(VALIDATOR2),
+          amount: coin(200, "uluna"),
+        }),
+        res.messages[0].msg
+      );
+
+      // without an amount the whole delegation moves
+      let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), redelegate(VALIDATOR, VALIDATOR2, None)).unwrap();
+      assert_eq!(
+        CosmosMsg::Staking(StakingMsg::Redelegate {
+          src_validator: String::from(VALIDATOR),
+          dst_validator: String::from(VALIDATOR2),
+          amount: coin(500, "uluna"),
+        }),
+        res.messages[0].msg
+      );
+    }
+
+    #[test]
+    fn try_rebalance() {
+      let mut deps = mock_dependencies(&[]);
+      let removed = "terravaloper1removed";
+      deps.querier.with_delegations(&[VALIDATOR, VALIDATOR2, removed], &[(VALIDATOR, 600), (removed, 100)]);
+
+      let msg = InstantiateMsg {
+        validators: vec![validator(VALIDATOR, 1), validator(VALIDATOR2, 1)],
+        ..default_instantiate_msg()
+      };
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), ExecuteMsg::Rebalance {}).unwrap_err();
+      assert!(matches!(err, ContractError::Unauthorized {}));
+
+      // 700 delegated in total, so each validator in the set should hold 350
+      let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), ExecuteMsg::Rebalance {}).unwrap();
+      assert_eq!(
+        vec![
+          CosmosMsg::Staking(StakingMsg::Redelegate {
+            src_validator: String::from(VALIDATOR),
+            dst_validator: String::from(VALIDATOR2),
+            amount: coin(250, "uluna"),
+          }),
+          CosmosMsg::Staking(StakingMsg::Redelegate {
+            src_validator: String::from(removed),
+            dst_validator: String::from(VALIDATOR2),
+            amount: coin(100, "uluna"),
+          }),
+        ],
+        res.messages.into_iter().map(|m| m.msg).collect::<Vec<_>>()
+      );
+      assert_eq!("350", res.attributes[1].value);
+
+      // a balanced set needs no redelegations
+      deps.querier.with_delegations(&[VALIDATOR, VALIDATOR2], &[(VALIDATOR, 350), (VALIDATOR2, 350)]);
+      let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), ExecuteMsg::Rebalance {}).unwrap();
+      assert!(res.messages.is_empty());
+    }
+
+    fn reply_ok(id: u64) -> Reply {
+      Reply { id, result: ContractResult::Ok(SubMsgExecutionResponse { events: vec![], data: None }) }
     }
 
     #[test]
     fn try_withdraw() {
+      let mut deps = mock_dependencies(&[]);
+      deps.querier.with_delegations(&[VALIDATOR, VALIDATOR2], &[(VALIDATOR, 500), (VALIDATOR2, 300)]);
+      deps.querier.with_exchange_rates(&[("uusd", Decimal::percent(9000))]);
+      deps.querier.with_balance(&[coin(100, "uluna"), coin(50, "uusd")]);
+
+      let msg = default_instantiate_msg();
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), ExecuteMsg::Withdraw {}).unwrap_err();
+      assert!(matches!(err, ContractError::Unauthorized {}));
+
+      // 80 uluna are unbonding, and rewards were paid out along the way when the delegations changed
+      let msg = ExecuteMsg::StartUndelegation { amount: Uint128::new(80) };
+      let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+      let mut env = mock_env();
+      env.block.time = env.block.time.plus_seconds(UNBONDING_PERIOD + 1);
+      deps.querier.with_balance(&[coin(180, "uluna"), coin(50, "uusd")]);
+
+      // rewards are claimed from every validator, with a reply after the last claim
+      let res = execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::Withdraw {}).unwrap();
+      assert_eq!(
+        vec![
+          SubMsg::new(DistributionMsg::WithdrawDelegatorReward { validator: String::from(VALIDATOR) }),
+          SubMsg::reply_on_success(DistributionMsg::WithdrawDelegatorReward { validator: String::from(VALIDATOR2) }, WITHDRAW_REWARDS_REPLY_ID),
+        ],
+        res.messages
+      );
+      assert_eq!(Uint128::new(80), UNBONDING_RECEIVED.load(&deps.storage).unwrap());
+
+      // everything but the returned luna is swapped, and denoms without a market rate are left alone
+      deps.querier.with_balance(&[coin(210, "uluna"), coin(250, "uusd"), coin(10, "ueur")]);
+      let res = reply(deps.as_mut(), env.clone(), reply_ok(WITHDRAW_REWARDS_REPLY_ID)).unwrap();
+      assert_eq!(
+        vec![SubMsg::reply_on_success(create_swap_msg(coin(250, "uusd"), String::from("uluna")), SWAP_REWARDS_REPLY_ID)],
+        res.messages
+      );
+
+      // the 130 uluna of rewards plus the luna the swap returned goes to the owner
+      deps.querier.with_balance(&[coin(232, "uluna"), coin(10, "ueur")]);
+      let res = reply(deps.as_mut(), env.clone(), reply_ok(SWAP_REWARDS_REPLY_ID)).unwrap();
+      assert_eq!(
+        vec![SubMsg::new(BankMsg::Send { to_address: String::from("creator"), amount: coins(152, "uluna") })],
+        res.messages
+      );
+      assert!(PENDING_WITHDRAW.may_load(&deps.storage).unwrap().is_none());
+
+      // luna rewards alone are sent without a swap step
+      let _res = execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::Withdraw {}).unwrap();
+      deps.querier.with_balance(&[coin(88, "uluna"), coin(10, "ueur")]);
+      let res = reply(deps.as_mut(), env.clone(), reply_ok(WITHDRAW_REWARDS_REPLY_ID)).unwrap();
+      assert_eq!(
+        vec![SubMsg::new(BankMsg::Send { to_address: String::from("creator"), amount: coins(8, "uluna") })],
+        res.messages
+      );
+
+      // the returned luna is still there to claim
+      deps.querier.with_balance(&[coin(80, "uluna"), coin(10, "ueur")]);
+      let res = execute(deps.as_mut(), env, mock_info("creator", &[]), ExecuteMsg::ClaimUnbonded {}).unwrap();
+      assert_eq!(
+        vec![SubMsg::new(BankMsg::Send { to_address: String::from("creator"), amount: coins(80, "uluna") })],
+        res.messages
+      );
+
+      let err = reply(deps.as_mut(), mock_env(), reply_ok(7)).unwrap_err();
+      assert!(matches!(err, ContractError::UnknownReplyId { id: 7 }));
+    }
+
+    #[test]
+    fn try_withdraw_without_delegations() {
+      let mut deps = mock_dependencies(&[]);
+      deps.querier.with_delegations(&[VALIDATOR], &[]);
+
+      let msg = default_instantiate_msg();
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), ExecuteMsg::Withdraw {}).unwrap_err();
+      assert!(matches!(err, ContractError::NothingToWithdraw {}));
+
+      // rewards paid out by the last undelegation are still sent, without claiming anything
+      deps.querier.with_balance(&coins(20, "uluna"));
+      let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), ExecuteMsg::Withdraw {}).unwrap();
+      assert_eq!(
+        vec![SubMsg::new(BankMsg::Send { to_address: String::from("creator"), amount: coins(20, "uluna") })],
+        res.messages
+      );
+    }
+
+    #[test]
+    fn liquid_staking() {
+      let mut deps = mock_dependencies(&[]);
+      deps.querier.with_delegations(&[VALIDATOR, VALIDATOR2], &[]);
+
+      let msg = InstantiateMsg {
+        validators: vec![validator(VALIDATOR, 1), validator(VALIDATOR2, 1)],
+        receipt_token: Some(Addr::unchecked(RECEIPT)),
+        ..default_instantiate_msg()
+      };
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+      let err = execute(deps.as_mut(), mock_env(), mock_info("alice", &coins(1_000, "uusd")), ExecuteMsg::Deposit {}).unwrap_err();
+      assert!(matches!(err, ContractError::UnsupportedDenom { .. }));
+
+      // the first deposit mints one receipt per uluna
+      let res = execute(deps.as_mut(), mock_env(), mock_info("alice", &coins(1_000, "uluna")), ExecuteMsg::Deposit {}).unwrap();
+      assert_eq!(
+        vec![
+          CosmosMsg::Staking(StakingMsg::Delegate { validator: String::from(VALIDATOR), amount: coin(500, "uluna") }),
+          CosmosMsg::Staking(StakingMsg::Delegate { validator: String::from(VALIDATOR2), amount: coin(500, "uluna") }),
+          CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: String::from(RECEIPT),
+            funds: vec![],
+            msg: to_binary(&Cw20ExecuteMsg::Mint { recipient: String::from("alice"), amount: Uint128::new(1_000) }).unwrap(),
+          }),
+        ],
+        res.messages.into_iter().map(|m| m.msg).collect::<Vec<_>>()
+      );
+
+      // a purchase delegates another 100 uluna, which are the owner's
+      deps.querier.with_delegations(&[VALIDATOR, VALIDATOR2], &[(VALIDATOR, 500), (VALIDATOR2, 500)]);
+      deps.querier.with_oracle_price(Decimal::one());
+      deps.querier.with_token_balances(&[(&TOKEN.to_string(), &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::new(1_000))])]);
+      let buy = ExecuteMsg::Buy { min_tokens_out: None, max_price: None, deadline: None };
+      let _res = execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(100, "uluna")), buy).unwrap();
+
+      // the pool holds 1000 of the 1100 delegated, so it earns 100 of the 110 uluna of rewards
+      deps.querier.with_delegations(&[VALIDATOR, VALIDATOR2], &[(VALIDATOR, 600), (VALIDATOR2, 500)]);
+      let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), ExecuteMsg::Withdraw {}).unwrap();
+      deps.querier.with_balance(&coins(110, "uluna"));
+      let res = reply(deps.as_mut(), mock_env(), reply_ok(WITHDRAW_REWARDS_REPLY_ID)).unwrap();
+      assert_eq!(
+        vec![
+          CosmosMsg::Staking(StakingMsg::Delegate { validator: String::from(VALIDATOR), amount: coin(50, "uluna") }),
+          CosmosMsg::Staking(StakingMsg::Delegate { validator: String::from(VALIDATOR2), amount: coin(50, "uluna") }),
+          CosmosMsg::Bank(BankMsg::Send { to_address: String::from("creator"), amount: coins(10, "uluna") }),
+        ],
+        res.messages.into_iter().map(|m| m.msg).collect::<Vec<_>>()
+      );
+
+      // later deposits get fewer receipts per uluna
+      deps.querier.with_balance(&[]);
+      deps.querier.with_delegations(&[VALIDATOR, VALIDATOR2], &[(VALIDATOR, 650), (VALIDATOR2, 550)]);
+      let res = execute(deps.as_mut(), mock_env(), mock_info("bob", &coins(1_100, "uluna")), ExecuteMsg::Deposit {}).unwrap();
+      assert_eq!("1000", res.attributes[2].value);
+      deps.querier.with_delegations(&[VALIDATOR, VALIDATOR2], &[(VALIDATOR, 1_200), (VALIDATOR2, 1_100)]);
+      let res: LiquidStakingResponse = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::LiquidStaking {}).unwrap()).unwrap();
+      assert_eq!(
+        LiquidStakingResponse {
+          receipt_token: Some(Addr::unchecked(RECEIPT)),
+          staked: Uint128::new(2_200),
+          receipt_supply: Uint128::new(2_000),
+          exchange_rate: Decimal::percent(110),
+        },
+        res
+      );
+
+      // receipts are redeemed through the unbonding queue
+      let unbond = |sender: &str| ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: String::from(sender),
+        amount: Uint128::new(500),
+        msg: to_binary(&Cw20HookMsg::Unbond {}).unwrap(),
+      });
+      let err = execute(deps.as_mut(), mock_env(), mock_info(TOKEN, &[]), unbond("alice")).unwrap_err();
+      assert!(matches!(err, ContractError::Unauthorized {}));
+      let res = execute(deps.as_mut(), mock_env(), mock_info(RECEIPT, &[]), unbond("alice")).unwrap();
+      assert_eq!(
+        vec![
+          CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: String::from(RECEIPT),
+            funds: vec![],
+            msg: to_binary(&Cw20ExecuteMsg::Burn { amount: Uint128::new(500) }).unwrap(),
+          }),
+          CosmosMsg::Staking(StakingMsg::Undelegate { validator: String::from(VALIDATOR), amount: coin(287, "uluna") }),
+          CosmosMsg::Staking(StakingMsg::Undelegate { validator: String::from(VALIDATOR2), amount: coin(263, "uluna") }),
+        ],
+        res.messages.into_iter().map(|m| m.msg).collect::<Vec<_>>()
+      );
+
+      // the owner cannot undelegate what the pool still holds
+      deps.querier.with_delegations(&[VALIDATOR, VALIDATOR2], &[(VALIDATOR, 913), (VALIDATOR2, 837)]);
+      let msg = ExecuteMsg::StartUndelegation { amount: Uint128::new(101) };
+      let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap_err();
+      assert!(matches!(err, ContractError::InsufficientDelegation { .. }));
+
+      // a slash lowers the rate for receipt holders, who bear their share of it
+      deps.querier.with_delegations(&[VALIDATOR, VALIDATOR2], &[(VALIDATOR, 822), (VALIDATOR2, 753)]);
+      let res: LiquidStakingResponse = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::LiquidStaking {}).unwrap()).unwrap();
+      assert_eq!((Uint128::new(1_485), Decimal::percent(99)), (res.staked, res.exchange_rate));
+
+      // once matured, the luna goes to the receipt holder
+      let mut env = mock_env();
+      env.block.time = env.block.time.plus_seconds(UNBONDING_PERIOD + 1);
+      deps.querier.with_balance(&coins(550, "uluna"));
+      let res: UnbondingResponse = from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::Unbonding { address: Some(String::from("alice")) }).unwrap()).unwrap();
+      assert_eq!(UnbondingResponse { total_unbonding: Uint128::new(550), matured: Uint128::new(550), claimable: Uint128::new(550) }, res);
+      let err = execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::ClaimUnbonded {}).unwrap_err();
+      assert!(matches!(err, ContractError::NothingToClaim {}));
+      let res = execute(deps.as_mut(), env, mock_info("alice", &[]), ExecuteMsg::ClaimUnbonded {}).unwrap();
+      assert_eq!(
+        vec![SubMsg::new(BankMsg::Send { to_address: String::from("alice"), amount: coins(550, "uluna") })],
+        res.messages
+      );
+    }
+
+    #[test]
+    fn deposit_requires_receipt_token() {
+      let mut deps = mock_dependencies(&[]);
+      deps.querier.with_delegations(&[VALIDATOR], &[]);
+
+      let msg = default_instantiate_msg();
+      let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
 
+      let err = execute(deps.as_mut(), mock_env(), mock_info("alice", &coins(1_000, "uluna")), ExecuteMsg::Deposit {}).unwrap_err();
+      assert!(matches!(err, ContractError::LiquidStakingDisabled {}));
     }
 }
@@ -1,4 +1,4 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{Decimal, StdError, Uint128};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -25,6 +25,57 @@ pub enum ContractError {
     #[error("Only uluna should be passed")]
     CoinMismatch {},
 
+    #[error("Denom {denom} is not accepted")]
+    UnsupportedDenom { denom: String },
+
     #[error("Not enough coins remain in contract")]
     InsufficientCoinsInContract {},
+
+    #[error("Oracle price must be positive")]
+    InvalidPrice {},
+
+    #[error("Oracle price is stale: last updated {age} seconds ago, max age is {max_age}")]
+    StalePrice { age: u64, max_age: u64 },
+
+    #[error("Oracle price feed is paused")]
+    PriceFeedPaused {},
+
+    #[error("Deadline {deadline} has passed")]
+    DeadlineExceeded { deadline: u64 },
+
+    #[error("Price {price} is above the maximum of {max_price}")]
+    MaxPriceExceeded { price: Decimal, max_price: Decimal },
+
+    #[error("Validator set must be non-empty, with positive weights and no duplicates")]
+    InvalidValidatorSet {},
+
+    #[error("Validator {validator} does not exist")]
+    UnknownValidator { validator: String },
+
+    #[error("Cannot undelegate {amount}, only {delegated} is delegated")]
+    InsufficientDelegation { amount: Uint128, delegated: Uint128 },
+
+    #[error("No delegation to {validator}")]
+    NoDelegation { validator: String },
+
+    #[error("Cannot redelegate {amount}, only {available} can be redelegated now")]
+    RedelegationUnavailable { amount: Uint128, available: Uint128 },
+
+    #[error("Cannot redelegate to the same validator")]
+    SameValidator {},
+
+    #[error("Nothing is delegated, so there are no rewards to withdraw")]
+    NothingToWithdraw {},
+
+    #[error("No matured unbonding to claim")]
+    NothingToClaim {},
+
+    #[error("Liquid staking is not enabled")]
+    LiquidStakingDisabled {},
+
+    #[error("Unknown reply id {id}")]
+    UnknownReplyId { id: u64 },
+
+    #[error("{tokens_out} tokens out is below the minimum of {min_tokens_out}")]
+    MinTokensOutNotMet { tokens_out: Uint128, min_tokens_out: Uint128 },
 }
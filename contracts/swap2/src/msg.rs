@@ -1,26 +1,55 @@
-use cosmwasm_std::{Addr, Uint128};
+use cosmwasm_std::{Addr, Decimal, Uint128};
+use cw20::Cw20ReceiveMsg;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+pub use crate::state::{UnbondingEntry, ValidatorWeight};
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InstantiateMsg {
     pub token_address: Addr,
     pub oracle_address: Addr,
+    pub max_price_age: Option<u64>,
+    pub validators: Vec<ValidatorWeight>,
+    pub receipt_token: Option<Addr>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ExecuteMsg {
-    // Buy
-    Buy {},
+    // Buy reverts unless at least `min_tokens_out` are bought, at no more than `max_price`, before `deadline`
+    Buy { min_tokens_out: Option<Uint128>, max_price: Option<Decimal>, deadline: Option<u64> },
 
-    // Withdraw
-    Withdraw { amount: u64 }, // Step 1: claim rewards from validators
-    WithdrawStep2ConvertRewardsToLuna { amount: u64 },
-    WithdrawStep3SendLuna { amount: u64 },
+    // Withdraw claims the staking rewards, swaps the ones paid in other denoms to luna and sends
+    // all of the luna received to the owner
+    Withdraw {},
 
-    // StartUndelegation
+    // StartUndelegation undelegates `amount` across the current delegations and queues it until
+    // the unbonding period is over
     StartUndelegation { amount: Uint128 },
+    // ClaimUnbonded sends the luna of the sender's matured unbonding entries
+    ClaimUnbonded {},
+
+    // UpdateValidators replaces the validator set that new delegations are split across
+    UpdateValidators { validators: Vec<ValidatorWeight> },
+
+    // Redelegate moves `amount` of the delegation to `src_validator` over to `dst_validator`, or all
+    // of it when no amount is given
+    Redelegate { src_validator: String, dst_validator: String, amount: Option<Uint128> },
+    // Rebalance redelegates stake until every validator holds its weighted share of the total
+    Rebalance {},
+
+    // Deposit delegates the uluna sent and mints receipt tokens for it at the current exchange rate
+    Deposit {},
+    // Receive handles receipt tokens sent through the token contract, see Cw20HookMsg
+    Receive(Cw20ReceiveMsg),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Cw20HookMsg {
+    // Unbond burns the receipts sent and queues the luna they are worth for the sender
+    Unbond {},
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -29,15 +58,56 @@ pub enum QueryMsg {
     QueryTokenAddress {},
     QueryPrice {},
     Balance { address: Addr },
+    // Delegations returns the weight of every validator in the set and what the contract has
+    // delegated to it, followed by any validator outside the set that still holds a delegation
+    Delegations {},
+    // UnbondingQueue lists pending unbonding entries in the order they started
+    UnbondingQueue { start_after: Option<u64>, limit: Option<u32> },
+    // Unbonding sums up the entries of one recipient, or of everyone when no address is given
+    Unbonding { address: Option<String> },
+    LiquidStaking {},
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-#[serde(rename_all = "snake_case")]
-pub struct PriceResponse {
-  pub price: u64
+pub struct QueryTokenAddressResponse {
+    pub token_address: Addr,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-pub struct QueryTokenAddressResponse {
-    pub token_address: Addr,
+pub struct ValidatorDelegation {
+    pub validator: String,
+    // zero for validators that have been removed from the set
+    pub weight: u64,
+    pub delegated: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DelegationsResponse {
+    pub delegations: Vec<ValidatorDelegation>,
+    pub total_delegated: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct UnbondingQueueResponse {
+    pub entries: Vec<UnbondingEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct UnbondingResponse {
+    // uluna in every entry that has not been claimed
+    pub total_unbonding: Uint128,
+    // uluna in entries past their completion time
+    pub matured: Uint128,
+    // what ClaimUnbonded would send now, which is less than `matured` when the chain returned less
+    pub claimable: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LiquidStakingResponse {
+    pub receipt_token: Option<Addr>,
+    // uluna the pool's part of the delegations is worth, after any slash
+    pub staked: Uint128,
+    pub receipt_supply: Uint128,
+    // uluna per receipt token
+    pub exchange_rate: Decimal,
 }
@@ -1,14 +1,84 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::Addr;
-use cw_storage_plus::Item;
+use cosmwasm_std::{Addr, Uint128};
+use cw_storage_plus::{Item, Map, U64Key};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct State {
     pub owner: Addr,
     pub token_address: Addr,
     pub oracle_address: Addr,
+    // oracle prices older than this (in seconds) are rejected
+    pub max_price_age: Option<u64>,
+    // the luna paid for tokens is delegated across these validators in proportion to their weights
+    pub validators: Vec<ValidatorWeight>,
+    // CW20 receipt minted for liquid-staking deposits, with this contract as its minter. Deposits
+    // are disabled without one.
+    pub receipt_token: Option<Addr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ValidatorWeight {
+    // operator address, e.g. terravaloper1...
+    pub address: String,
+    pub weight: u64,
+}
+
+// A withdrawal in progress, carried between the replies of its steps. It only lives for the
+// duration of the transaction that started it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingWithdraw {
+    // uluna in the balance that is not held for unbonding claims, once rewards were claimed
+    pub luna_rewards: Uint128,
+    // uluna balance before the other reward denoms were swapped
+    pub luna_before_swaps: Uint128,
+    // the liquid-staking pool earns its share of the total delegation's rewards
+    pub pool_shares: Uint128,
+    pub total_shares: Uint128,
+    pub total_delegated: Uint128,
+}
+
+// Luna delegated on behalf of receipt holders, held as shares of everything the contract has
+// delegated, so a slash lowers its value along with the luna delegated by purchases. Receipts are
+// worth the pool's part of the delegations divided by `receipt_supply`, which grows as Withdraw
+// restakes the pool's rewards.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct LiquidStakingPool {
+    pub shares: Uint128,
+    pub receipt_supply: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct UnbondingEntry {
+    pub id: u64,
+    // who the luna is sent to once claimed
+    pub recipient: Addr,
+    pub validator: String,
+    // uluna undelegated
+    pub amount: Uint128,
+    pub start_time: u64,
+    // when the chain returns the luna to the contract
+    pub completion_time: u64,
+    // uluna the chain actually returned, which can be less than `amount` after a slash. None
+    // until the entry has matured and been settled.
+    pub received: Option<Uint128>,
 }
 
 pub const STATE: Item<State> = Item::new("state");
+
+pub const PENDING_WITHDRAW: Item<PendingWithdraw> = Item::new("pending_withdraw");
+
+pub const LIQUID_STAKING: Item<LiquidStakingPool> = Item::new("liquid_staking");
+
+// shares of the contract's delegations, held by the liquid-staking pool and, for the rest, by the owner
+pub const DELEGATION_SHARES: Item<Uint128> = Item::new("delegation_shares");
+
+// undelegations that have not been claimed yet, keyed by id in the order they started
+pub const UNBONDING_QUEUE: Map<U64Key, UnbondingEntry> = Map::new("unbonding_queue");
+
+// last id handed out to an unbonding entry
+pub const UNBONDING_COUNT: Item<u64> = Item::new("unbonding_count");
+
+// luna returned for settled entries that has not been claimed yet, which belongs to their recipients
+pub const UNBONDING_RECEIVED: Item<Uint128> = Item::new("unbonding_received");
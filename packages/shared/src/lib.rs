@@ -1,2 +1,3 @@
+pub mod math;
 pub mod oracle;
 pub mod querier;
\ No newline at end of file
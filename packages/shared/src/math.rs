@@ -0,0 +1,64 @@
+use cosmwasm_std::{Decimal, Fraction, StdError, StdResult, Uint128, Uint256};
+use std::convert::TryInto;
+
+/// ## Description
+/// Returns how many whole units of an asset `amount` buys at `price`, rounded down.
+/// ## Params
+/// * **amount** is the object of type [`Uint128`]. The amount offered, in units of the quote asset.
+///
+/// * **price** is the object of type [`Decimal`]. The price in units of the quote asset; must not be zero.
+pub fn div_floor(amount: Uint128, price: Decimal) -> Uint128 {
+    amount.multiply_ratio(price.denominator(), price.numerator())
+}
+
+/// ## Description
+/// Returns the cost of `amount` units of an asset at `price`, rounded up.
+/// ## Params
+/// * **amount** is the object of type [`Uint128`]. The amount bought, in units of the base asset.
+///
+/// * **price** is the object of type [`Decimal`]. The price in units of the quote asset.
+pub fn mul_ceil(amount: Uint128, price: Decimal) -> Uint128 {
+    let floor = amount * price;
+    if floor.full_mul(price.denominator()) < amount.full_mul(price.numerator()) {
+        floor + Uint128::new(1)
+    } else {
+        floor
+    }
+}
+
+/// ## Description
+/// Returns the product of two prices, rounded down, e.g. turns a price in uluna and a uluna price
+/// in uusd into a price in uusd.
+/// ## Params
+/// * **a** is the object of type [`Decimal`].
+///
+/// * **b** is the object of type [`Decimal`].
+pub fn decimal_mul(a: Decimal, b: Decimal) -> StdResult<Decimal> {
+    let atomics: Uint128 = (Uint128::from(a.numerator()).full_mul(b.numerator())
+        / Uint256::from(b.denominator()))
+    .try_into()
+    .map_err(|_| StdError::generic_err("Price multiplication overflow"))?;
+    Ok(Decimal::from_ratio(atomics, Decimal::one().denominator()))
+}
+
+/// ## Description
+/// Returns `base` raised to the power of `exp`, rounding down after every multiplication.
+/// ## Params
+/// * **base** is the object of type [`Decimal`].
+///
+/// * **exp** is the object of type [`u128`].
+pub fn decimal_pow(base: Decimal, exp: u128) -> StdResult<Decimal> {
+    let mut result = Decimal::one();
+    let mut base = base;
+    let mut exp = exp;
+    while exp > 0 {
+        if exp % 2 == 1 {
+            result = decimal_mul(result, base)?;
+        }
+        exp /= 2;
+        if exp > 0 {
+            base = decimal_mul(base, base)?;
+        }
+    }
+    Ok(result)
+}
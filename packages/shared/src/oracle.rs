@@ -1,8 +1,58 @@
+use cosmwasm_std::{Api, Decimal, StdResult};
+use cw_asset::{AssetInfo, AssetInfoUnchecked};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+// A pair of assets, priced as units of `quote` per unit of `base`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Pair {
+    pub base: AssetInfoUnchecked,
+    pub quote: AssetInfoUnchecked,
+}
+
+impl Pair {
+    pub fn new(base: AssetInfo, quote: AssetInfo) -> Self {
+        Pair { base: base.into(), quote: quote.into() }
+    }
+
+    /// ## Description
+    /// Validates both assets and returns the key the pair is stored under in the oracle,
+    /// e.g. `cw20:terra1...|native:uluna`.
+    /// ## Params
+    /// * **api** is the object of type [`Api`].
+    pub fn key(&self, api: &dyn Api) -> StdResult<String> {
+        Ok(format!("{}|{}", self.base.check(api)?, self.quote.check(api)?))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    QueryPrice { pair: Pair },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceStatus {
+    Active,
+    // the feed tripped a circuit breaker and the price must not be traded on
+    Paused,
+}
+
+// Sent by the oracle to each approved subscriber whenever it publishes a new price. Subscribing
+// contracts handle it as a variant of their own ExecuteMsg.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CallbackMsg {
+    PriceUpdate { pair: Pair, price: PriceResponse },
+}
+
 // We define a custom struct for each query response
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct PriceResponse {
-    pub price: u64,
+    pub price: Decimal,
+    // block height and time (in seconds) of the update behind the price
+    pub last_updated_height: u64,
+    pub last_updated_time: u64,
+    pub status: PriceStatus,
 }
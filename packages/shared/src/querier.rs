@@ -1,8 +1,9 @@
 use cosmwasm_std::{
-    to_binary, Addr, AllBalanceResponse, BalanceResponse, BankQuery, Coin, QuerierWrapper,
-    QueryRequest, StdResult, Uint128, WasmQuery,
+    to_binary, Addr, AllBalanceResponse, BalanceResponse, BankQuery, Coin, Decimal,
+    QuerierWrapper, QueryRequest, StdError, StdResult, Uint128, WasmQuery,
 };
 use cw20::{BalanceResponse as Cw20BalanceResponse, Cw20QueryMsg, TokenInfoResponse};
+use terra_cosmwasm::{ExchangeRatesResponse, TerraQuerier};
 /// ## Description
 /// Returns the balance of the denom at the specified account address.
 /// ## Params
@@ -42,7 +43,7 @@ pub fn query_all_balances(querier: &QuerierWrapper, account_addr: &Addr) -> StdR
 /// * **querier** is the object of type [`QuerierWrapper`].
 ///
 /// * **contract_addr** is the object of type [`Addr`]. Sets the address of the contract for which
-/// the balance will be requested
+///   the balance will be requested
 ///
 /// * **account_addr** is the object of type [`Addr`].
 pub fn query_token_balance(
@@ -93,3 +94,35 @@ pub fn query_supply(querier: &QuerierWrapper, contract_addr: &Addr) -> StdResult
     }))?;
     Ok(res.total_supply)
 }
+
+/// ## Description
+/// Returns how many units of `quote_denom` one unit of `base_denom` is worth, according to the
+/// Terra market module.
+/// ## Params
+/// * **querier** is the object of type [`QuerierWrapper`].
+///
+/// * **base_denom** is the object of type [`str`].
+///
+/// * **quote_denom** is the object of type [`str`].
+pub fn query_exchange_rate(
+    querier: &QuerierWrapper,
+    base_denom: &str,
+    quote_denom: &str,
+) -> StdResult<Decimal> {
+    if base_denom == quote_denom {
+        return Ok(Decimal::one());
+    }
+
+    let res: ExchangeRatesResponse =
+        TerraQuerier::new(querier).query_exchange_rates(base_denom, vec![quote_denom])?;
+    res.exchange_rates
+        .into_iter()
+        .find(|item| item.quote_denom == quote_denom)
+        .map(|item| item.exchange_rate)
+        .ok_or_else(|| {
+            StdError::generic_err(format!(
+                "No exchange rate for {} in {}",
+                base_denom, quote_denom
+            ))
+        })
+}
@@ -1,24 +1,19 @@
 use cosmwasm_std::{QueryRequest};
-use cosmwasm_std::testing::{MockApi, MockQuerier, MockStorage};
+use cosmwasm_std::testing::{mock_env, MockApi, MockQuerier, MockStorage};
 use cosmwasm_std::{Coin, OwnedDeps, Querier, WasmQuery, QuerierResult, from_binary, 
-  to_binary, from_slice, SystemError, SystemResult, ContractResult, Addr, Uint128};
+  to_binary, from_slice, SystemError, SystemResult, ContractResult, Addr, Decimal, Fraction, Uint128,
+  FullDelegation, Validator, coin};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use terra_cosmwasm::{
-    TerraQueryWrapper
+    ExchangeRateItem, ExchangeRatesResponse, TerraQuery, TerraQueryWrapper, TerraRoute
 };
 use cw20::BalanceResponse as Cw20BalanceResponse;
+use shared::oracle::{Pair, PriceResponse, PriceStatus};
 use std::collections::HashMap;
 
 pub const MOCK_CONTRACT_ADDR: &str = "cosmos2contract";
 
-// We define a custom struct for each query response
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-pub struct PriceResponse {
-    pub price: u64,
-}
-// TODO: How can I import directly from oracle.rs file?
-
 pub fn mock_dependencies(
     contract_balance: &[Coin],
 ) -> OwnedDeps<MockStorage, MockApi, WasmMockQuerier> {
@@ -36,13 +31,14 @@ pub fn mock_dependencies(
 pub struct WasmMockQuerier {
     base: MockQuerier<TerraQueryWrapper>,
     price_querier: PriceQuerier,
-    token_querier: TokenQuerier
+    token_querier: TokenQuerier,
+    exchange_rate_querier: ExchangeRateQuerier,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
-    QueryPrice { },
+    QueryPrice { pair: Pair },
     Balance { address: Addr }
 }
 
@@ -66,7 +62,8 @@ impl WasmMockQuerier {
       WasmMockQuerier {
           base,
           price_querier: PriceQuerier::default(),
-          token_querier: TokenQuerier::default()
+          token_querier: TokenQuerier::default(),
+          exchange_rate_querier: ExchangeRateQuerier::default(),
       }
   }
 
@@ -74,41 +71,88 @@ impl WasmMockQuerier {
     match &request {
       QueryRequest::Wasm(WasmQuery::Smart { contract_addr, msg }) => {
         if contract_addr == "oracle000" {
-          let response: PriceResponse = PriceResponse { price: self.price_querier.price };
+          let response: PriceResponse = self.price_querier.price.clone();
           match from_binary(msg).unwrap() {
-            QueryMsg::QueryPrice {} => { 
-              return SystemResult::Ok(
-                ContractResult::Ok(to_binary(&response).unwrap())
-              );
-            }
-            _ => {
-              return SystemResult::Err(SystemError::InvalidRequest {
-                error: "Did not match on the query message".to_string(),
-                request: msg.as_slice().into()});
+            QueryMsg::QueryPrice { .. } => {
+              SystemResult::Ok(ContractResult::Ok(to_binary(&response).unwrap()))
             }
+            _ => SystemResult::Err(SystemError::InvalidRequest {
+              error: "Did not match on the query message".to_string(),
+              request: msg.as_slice().into(),
+            }),
           }
         } else {
           match from_binary(msg).unwrap() {
             QueryMsg::Balance { address } => {
-              let balance = self.token_querier.get_balance(contract_addr, &address.to_string());
-              return SystemResult::Ok(ContractResult::Ok(
+              let balance = self.token_querier.get_balance(contract_addr, address.as_str());
+              SystemResult::Ok(ContractResult::Ok(
                   to_binary(&Cw20BalanceResponse { balance }).unwrap(),
-              ));
-            }
-            _ => { 
-              return SystemResult::Err(SystemError::InvalidRequest {
-                error: "Did not match on the query message".to_string(),
-                request: msg.as_slice().into()});
+              ))
             }
+            _ => SystemResult::Err(SystemError::InvalidRequest {
+              error: "Did not match on the query message".to_string(),
+              request: msg.as_slice().into(),
+            }),
+          }
+        }
+      }
+      QueryRequest::Custom(TerraQueryWrapper { route: TerraRoute::Oracle, query_data }) => {
+        match query_data {
+          TerraQuery::ExchangeRates { base_denom, quote_denoms } => {
+            let response = self.exchange_rate_querier.query(base_denom, quote_denoms);
+            SystemResult::Ok(ContractResult::Ok(to_binary(&response).unwrap()))
           }
+          _ => panic!("DO NOT ENTER HERE"),
         }
       }
       _ => self.base.handle_query(request),
     }
   }
 
-  pub fn with_oracle_price(&mut self, price: u64) {
-    self.price_querier = PriceQuerier::new(price);
+  pub fn with_oracle_price(&mut self, price: Decimal) {
+    let env = mock_env();
+    self.price_querier = PriceQuerier::new(price, env.block.height, env.block.time.seconds());
+  }
+
+  pub fn with_oracle_price_updated_at(&mut self, price: Decimal, height: u64, time: u64) {
+    self.price_querier = PriceQuerier::new(price, height, time);
+  }
+
+  pub fn with_oracle_status(&mut self, status: PriceStatus) {
+    self.price_querier.price.status = status;
+  }
+
+  // rates are the price of one uluna in each denom, as the market module quotes them
+  pub fn with_exchange_rates(&mut self, rates: &[(&str, Decimal)]) {
+    self.exchange_rate_querier = ExchangeRateQuerier::new(rates);
+  }
+
+  pub fn with_balance(&mut self, balance: &[Coin]) {
+    self.base.update_balance(MOCK_CONTRACT_ADDR, balance.to_vec());
+  }
+
+  // validators exist with no commission, and the contract has delegated `amount` uluna to each
+  pub fn with_delegations(&mut self, validators: &[&str], delegations: &[(&str, u128)]) {
+    let validators: Vec<Validator> = validators
+      .iter()
+      .map(|address| Validator {
+        address: address.to_string(),
+        commission: Decimal::zero(),
+        max_commission: Decimal::percent(10),
+        max_change_rate: Decimal::percent(1),
+      })
+      .collect();
+    let delegations: Vec<FullDelegation> = delegations
+      .iter()
+      .map(|(validator, amount)| FullDelegation {
+        delegator: Addr::unchecked(MOCK_CONTRACT_ADDR),
+        validator: validator.to_string(),
+        amount: coin(*amount, "uluna"),
+        can_redelegate: coin(*amount, "uluna"),
+        accumulated_rewards: vec![],
+      })
+      .collect();
+    self.base.update_staking("uluna", &validators, &delegations);
   }
 
   pub fn with_token_balances(&mut self, balances: &[(&String, &[(&String, &Uint128)])]) {
@@ -116,9 +160,9 @@ impl WasmMockQuerier {
   }
 }
 
-#[derive(Clone, Default)]
+#[derive(Clone)]
 pub struct PriceQuerier {
-    price: u64,
+    price: PriceResponse,
 }
 
 #[derive(Clone, Default)]
@@ -126,14 +170,60 @@ pub struct TokenQuerier {
     balances: HashMap<String, HashMap<String, Uint128>>,
 }
 
+#[derive(Clone, Default)]
+pub struct ExchangeRateQuerier {
+    rates: HashMap<String, Decimal>,
+}
+
+impl ExchangeRateQuerier {
+    pub fn new(rates: &[(&str, Decimal)]) -> Self {
+        ExchangeRateQuerier {
+            rates: rates.iter().map(|(denom, rate)| (denom.to_string(), *rate)).collect(),
+        }
+    }
+
+    fn rate(&self, denom: &str) -> Option<Decimal> {
+        match denom {
+            "uluna" => Some(Decimal::one()),
+            _ => self.rates.get(denom).copied(),
+        }
+    }
+
+    // cross rates go through luna, and unknown quote denoms are left out
+    pub fn query(&self, base_denom: &str, quote_denoms: &[String]) -> ExchangeRatesResponse {
+        let base_rate = self.rate(base_denom).expect("unknown base denom");
+        let exchange_rates = quote_denoms
+            .iter()
+            .filter_map(|quote_denom| {
+                self.rate(quote_denom).map(|quote_rate| ExchangeRateItem {
+                    quote_denom: quote_denom.clone(),
+                    exchange_rate: Decimal::from_ratio(quote_rate.numerator(), base_rate.numerator()),
+                })
+            })
+            .collect();
+        ExchangeRatesResponse { base_denom: base_denom.to_string(), exchange_rates }
+    }
+}
+
 impl PriceQuerier {
-    pub fn new(price: u64) -> Self {
+    pub fn new(price: Decimal, last_updated_height: u64, last_updated_time: u64) -> Self {
         PriceQuerier {
-            price: price,
+            price: PriceResponse {
+                price,
+                last_updated_height,
+                last_updated_time,
+                status: PriceStatus::Active,
+            },
         }
     }
 }
 
+impl Default for PriceQuerier {
+    fn default() -> Self {
+        PriceQuerier::new(Decimal::zero(), 0, 0)
+    }
+}
+
 impl TokenQuerier {
     pub fn new(balances: &[(&String, &[(&String, &Uint128)])]) -> Self {
         TokenQuerier {
@@ -142,9 +232,9 @@ impl TokenQuerier {
     }
 
     pub fn get_balance(&self, token_addr: &str, addr: &str) -> Uint128 {
-        let contract_balances = self.balances.get(&token_addr.to_string());
+        let contract_balances = self.balances.get(token_addr);
         match contract_balances {
-            Some(balances) => *balances.get(&addr.to_string()).unwrap_or(&Uint128::zero()),
+            Some(balances) => *balances.get(addr).unwrap_or(&Uint128::zero()),
             None => Uint128::zero(),
         }
     }
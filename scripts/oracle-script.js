@@ -8,15 +8,20 @@ console.log('Run other scripts...');
 
 let adminWallet = wallets.admin;
 let oracleAddress = addresses.testnet.oracle.contractAddresses.default;
+let tokenAddress = addresses.testnet.cw20_token.contractAddresses.default;
+
+// Lemons are priced in uluna.
+let pair = { base: { cw20: tokenAddress }, quote: { native: 'uluna' } };
 
 // Check current price.
-let res = await client.wasm.contractQuery(oracleAddress, { query_price: {} });
+let res = await client.wasm.contractQuery(oracleAddress, { query_price: { pair } });
 console.log(`Current price: ${res.price}`);
 
 // Write new price to oracle.
 var msg = new MsgExecuteContract(adminWallet.key.accAddress, oracleAddress, {
   update_price: {
-    price: 10,
+    pair,
+    price: '10',
   },
 });
 var tx = await adminWallet.createAndSignTx({ msgs: [msg] });
@@ -24,7 +29,7 @@ var result = await client.tx.broadcast(tx);
 console.log(result);
 
 // Check that new price was pushed successfully.
-res = await client.wasm.contractQuery(oracleAddress, { query_price: {} });
+res = await client.wasm.contractQuery(oracleAddress, { query_price: { pair } });
 console.log(`Current price: ${res.price}`);
 
 async function readJson(file) {
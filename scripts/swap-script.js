@@ -13,7 +13,8 @@ let swapAddress = addresses.testnet.swap.contractAddresses.default;
 const MAX_CONTRACT_BALANCE = 5 * Math.pow(10, 6);
 
 // Check current price.
-let price = await client.wasm.contractQuery(oracleAddress, { query_price: {} });
+let pair = { base: { cw20: tokenAddress }, quote: { native: 'uluna' } };
+let price = await client.wasm.contractQuery(oracleAddress, { query_price: { pair } });
 console.log(`Current price: ${price.price}`);
 
 // Check current balance.
@@ -60,7 +61,7 @@ if (res.balance < MAX_CONTRACT_BALANCE) {
 
 // Withdraw luna from swap contract
 var msg = new MsgExecuteContract(adminWallet.key.accAddress, swapAddress, {
-  withdraw: { amount: 3_000_000 },
+  withdraw: { assets: [{ info: { native: 'uluna' }, amount: '3000000' }] },
 });
 var tx = await adminWallet.createAndSignTx({ msgs: [msg] });
 var result = await client.tx.broadcast(tx);